@@ -0,0 +1,596 @@
+use crate::config::Config;
+use crate::ledger::sha256_file;
+use crate::markers;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Local};
+use log::{info, warn};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Archive settings, derived from the config.
+#[derive(Debug, Clone)]
+pub struct ArchiveOptions {
+    /// Archive root; files go to `YYYY/MM` below it.
+    pub root: PathBuf,
+    /// When set, keep the source's path relative to this dir below `YYYY/MM`.
+    pub source_root: Option<PathBuf>,
+    /// Retries after transient I/O errors, and the first delay (doubling).
+    pub retries: u32,
+    pub retry_delay: Duration,
+    /// Hash the source as it is moved, for the archive ledger.
+    pub hash_source: bool,
+}
+
+impl ArchiveOptions {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            root: expand_home(&cfg.paths.archive_dir),
+            retries: cfg.paths.archive_retries,
+            retry_delay: Duration::from_millis(cfg.paths.archive_retry_delay_ms),
+            source_root: cfg
+                .application
+                .preserve_source_subpath
+                .then(|| cfg.paths.default_open_dir.clone()),
+            hash_source: cfg.application.archive_ledger,
+        }
+    }
+}
+
+/// Where an archived file went.
+pub struct Archived {
+    pub dest: PathBuf,
+    /// SHA-256 and length of the source, with `hash_source`.
+    pub source_hash: Option<(String, u64)>,
+}
+
+/// Outcome of a background archive run.
+pub struct ArchiveResult {
+    pub src: PathBuf,
+    pub archived_at: DateTime<Local>,
+    pub archived: Result<Archived>,
+}
+
+/// Archive `src` into `<root>/YYYY/MM` on a worker thread, stamped with
+/// the current local time. The result arrives on the returned channel; a
+/// cross-device move copies the whole file, which can take a while.
+pub fn archive_in_background(src: PathBuf, opts: ArchiveOptions) -> Receiver<ArchiveResult> {
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("archive".into())
+        .spawn(move || {
+            let archived_at = Local::now();
+            let archived = archive_file_at(&src, &opts, archived_at);
+            let _ = tx.send(ArchiveResult {
+                src,
+                archived_at,
+                archived,
+            });
+        })
+        .expect("Failed to spawn archive worker");
+    rx
+}
+
+/// Archive `src` using `now` for the subfolder and filename stamp.
+pub fn archive_file_at(
+    src: &Path,
+    opts: &ArchiveOptions,
+    now: DateTime<Local>,
+) -> Result<Archived> {
+    let subdir = opts
+        .source_root
+        .as_deref()
+        .and_then(|root| source_subdir(src, root));
+    let dest = archive_destination(&opts.root, subdir.as_deref(), src, now);
+    if let Some(dir) = dest.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Both rename and copy would silently replace an existing file.
+    let dest = unused_path(dest);
+
+    let mut attempt = 0;
+    let (how, source_hash) = loop {
+        match move_file(src, &dest, opts.hash_source) {
+            Ok(moved) => break moved,
+            Err(e) if attempt < opts.retries && is_transient(&e) => {
+                attempt += 1;
+                let wait = opts.retry_delay * (1u32 << (attempt - 1).min(10));
+                warn!(
+                    "Archiving {} failed ({}); retry {} of {} in {:?}",
+                    src.display(),
+                    e,
+                    attempt,
+                    opts.retries,
+                    wait
+                );
+                thread::sleep(wait);
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    info!(
+        "Archived ({}): {} -> {}",
+        how,
+        src.display(),
+        dest.display()
+    );
+    Ok(Archived { dest, source_hash })
+}
+
+/// I/O errors a stalling network share produces and that may well not
+/// happen again.
+fn is_transient(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|c| c.downcast_ref::<std::io::Error>())
+        .any(|io| {
+            io.kind() == std::io::ErrorKind::TimedOut
+                || matches!(
+                    io.raw_os_error(),
+                    Some(libc::EIO | libc::ETIMEDOUT | libc::EAGAIN | libc::EHOSTDOWN)
+                )
+        })
+}
+
+/// Outcome of a background restore.
+pub struct RestoreResult {
+    pub archived: PathBuf,
+    pub original: PathBuf,
+    pub restored_at: DateTime<Local>,
+    /// The file's SHA-256 and length when asked for, see [`restore_archived`].
+    pub restored: Result<Option<(String, u64)>>,
+}
+
+/// [`restore_archived`] on a worker thread, as [`archive_in_background`]
+/// does for archiving: moving back across devices copies the whole file.
+pub fn restore_in_background(
+    archived: PathBuf,
+    original: PathBuf,
+    hash: bool,
+) -> Receiver<RestoreResult> {
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("restore".into())
+        .spawn(move || {
+            let restored_at = Local::now();
+            let restored = restore_archived(&archived, &original, hash);
+            let _ = tx.send(RestoreResult {
+                archived,
+                original,
+                restored_at,
+                restored,
+            });
+        })
+        .expect("Failed to spawn restore worker");
+    rx
+}
+
+/// Move an archived file back to where it came from, along with a markers
+/// sidecar exported next to it. Fails rather than overwrite if something
+/// new already sits at `original`. With `hash`, returns the file's SHA-256
+/// and length for the ledger.
+pub fn restore_archived(
+    archived: &Path,
+    original: &Path,
+    hash: bool,
+) -> Result<Option<(String, u64)>> {
+    if original.symlink_metadata().is_ok() {
+        return Err(anyhow!(
+            "{} already exists; not overwriting it",
+            original.display()
+        ));
+    }
+    if let Some(dir) = original.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let (how, file_hash) = move_file(archived, original, hash)?;
+    info!(
+        "Restored ({}): {} -> {}",
+        how,
+        archived.display(),
+        original.display()
+    );
+
+    // The audio is back either way; a sidecar that can't follow stays put.
+    let sidecar = markers::sidecar_path(archived, None);
+    if sidecar.exists() {
+        let back = markers::sidecar_path(original, None);
+        if back.symlink_metadata().is_ok() {
+            warn!(
+                "Markers left at {}: {} already exists",
+                sidecar.display(),
+                back.display()
+            );
+        } else if let Err(e) = move_file(&sidecar, &back, false) {
+            warn!(
+                "Moving markers back from {} failed: {:#}",
+                sidecar.display(),
+                e
+            );
+        }
+    }
+    Ok(file_hash.filter(|_| hash))
+}
+
+/// Rename, or copy and delete across filesystems. Returns which it did
+/// and, when known (always with `hash`), the source's SHA-256 and length
+/// as they were before the move.
+/// A copy is flushed and compared by hash before the source is deleted;
+/// on a mismatch the copy is removed and the source left alone.
+fn move_file(src: &Path, dest: &Path, hash: bool) -> Result<(&'static str, Option<(String, u64)>)> {
+    let src_hash = if hash { Some(sha256_file(src)?) } else { None };
+    if std::fs::rename(src, dest).is_ok() {
+        return Ok(("rename", src_hash));
+    }
+    if let Err(e) = std::fs::copy(src, dest) {
+        // Don't leave a partial copy behind (or in the way of a retry).
+        let _ = std::fs::remove_file(dest);
+        return Err(e.into());
+    }
+    let verified = File::open(dest)
+        .and_then(|f| f.sync_all())
+        .map_err(anyhow::Error::from)
+        .and_then(|_| {
+            let want = match &src_hash {
+                Some(h) => h.clone(),
+                None => sha256_file(src)?,
+            };
+            Ok((want, sha256_file(dest)?))
+        });
+    let want = match verified {
+        Ok((want, got)) if want == got => want,
+        Ok(((_, want_len), (_, got_len))) => {
+            let _ = std::fs::remove_file(dest);
+            let what = if got_len != want_len {
+                format!("is incomplete ({} of {} bytes)", got_len, want_len)
+            } else {
+                "differs from the original".to_owned()
+            };
+            return Err(anyhow!(
+                "copy at {} {}; original kept",
+                dest.display(),
+                what
+            ));
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(dest);
+            return Err(e.context(format!(
+                "verifying the copy at {} failed; original kept",
+                dest.display()
+            )));
+        }
+    };
+    std::fs::remove_file(src)?;
+    Ok(("copy+delete", Some(want)))
+}
+
+/// `path`, or the first of `name (1).ext`, `name (2).ext`, ... that
+/// doesn't exist yet.
+fn unused_path(path: PathBuf) -> PathBuf {
+    let taken = |p: &Path| p.symlink_metadata().is_ok();
+    if !taken(&path) {
+        return path;
+    }
+    let stem = path.file_stem().unwrap_or_default().to_os_string();
+    let ext = path.extension().map(OsStr::to_os_string);
+    (1..)
+        .map(|n| {
+            let mut name = stem.clone();
+            name.push(format!(" ({})", n));
+            if let Some(ext) = &ext {
+                name.push(".");
+                name.push(ext);
+            }
+            path.with_file_name(name)
+        })
+        .find(|p| !taken(p))
+        .expect("some numbered name is free")
+}
+
+/// `~/...` relative to HOME, as a shell would; anything else unchanged.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Directory of `src` relative to `source_root`, or `None` when `src` lies
+/// directly in it or outside it (flat layout).
+fn source_subdir(src: &Path, source_root: &Path) -> Option<PathBuf> {
+    let parent = src.parent()?;
+    let rel = match parent.strip_prefix(source_root) {
+        Ok(rel) => rel.to_path_buf(),
+        Err(_) => {
+            // Retry with symlinks/mount aliases resolved.
+            let parent = parent.canonicalize().ok()?;
+            let root = source_root.canonicalize().ok()?;
+            parent.strip_prefix(root).ok()?.to_path_buf()
+        }
+    };
+    (!rel.as_os_str().is_empty()).then_some(rel)
+}
+
+/// Destination for `src` under `root`:
+/// `root/YYYY/MM[/<subdir>]/<stem>_YYYYMMDD_HHMMSS.<ext>`.
+/// Pure function of its inputs; does not touch the filesystem.
+pub fn archive_destination(
+    root: &Path,
+    subdir: Option<&Path>,
+    src: &Path,
+    now: DateTime<Local>,
+) -> PathBuf {
+    let mut dest_dir = root
+        .join(format!("{:04}", now.year()))
+        .join(format!("{:02}", now.month()));
+    if let Some(sub) = subdir {
+        dest_dir.push(sub);
+    }
+
+    // Append suffix _YYYYMMDD_HHMMSS before the last extension only, so
+    // `a.v2.final.mp3` keeps its full stem. Built from OsStr so names that
+    // aren't valid UTF-8 survive unchanged; a trailing dot is dropped.
+    let stem = src
+        .file_stem()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| OsStr::new("file"));
+    let mut filename = stem.to_os_string();
+    filename.push(now.format("_%Y%m%d_%H%M%S").to_string());
+    if let Some(ext) = src.extension().filter(|e| !e.is_empty()) {
+        filename.push(".");
+        filename.push(ext);
+    }
+    dest_dir.join(filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, 3, 14, 5, 9).unwrap()
+    }
+
+    /// Archive to `archive/` in `dir`, without retries or hashing.
+    fn opts(dir: &Path) -> ArchiveOptions {
+        ArchiveOptions {
+            root: dir.join("archive"),
+            source_root: None,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            hash_source: false,
+        }
+    }
+
+    fn dest_name(src: &str) -> PathBuf {
+        archive_destination(Path::new("/arc"), None, Path::new(src), at())
+    }
+
+    #[test]
+    fn destination_is_dated_and_stamped() {
+        assert_eq!(
+            dest_name("/in/memo.mp3"),
+            Path::new("/arc/2024/06/memo_20240603_140509.mp3")
+        );
+    }
+
+    #[test]
+    fn destination_stamps_before_the_last_extension_only() {
+        assert_eq!(
+            dest_name("/in/interview.v2.final.mp3"),
+            Path::new("/arc/2024/06/interview.v2.final_20240603_140509.mp3")
+        );
+    }
+
+    #[test]
+    fn destination_keeps_unicode_and_spaces() {
+        assert_eq!(
+            dest_name("/in/Диктовка über café 1.wav"),
+            Path::new("/arc/2024/06/Диктовка über café 1_20240603_140509.wav")
+        );
+    }
+
+    #[test]
+    fn destination_without_extension() {
+        assert_eq!(
+            dest_name("/in/memo"),
+            Path::new("/arc/2024/06/memo_20240603_140509")
+        );
+    }
+
+    #[test]
+    fn destination_drops_a_trailing_dot() {
+        assert_eq!(
+            dest_name("/in/memo."),
+            Path::new("/arc/2024/06/memo_20240603_140509")
+        );
+    }
+
+    #[test]
+    fn destination_of_a_dotfile_keeps_its_name() {
+        assert_eq!(
+            dest_name("/in/.memo"),
+            Path::new("/arc/2024/06/.memo_20240603_140509")
+        );
+    }
+
+    #[test]
+    fn destination_with_subdir() {
+        let dest = archive_destination(
+            Path::new("/arc"),
+            Some(Path::new("clinic/ward 2")),
+            Path::new("/in/clinic/ward 2/memo.ogg"),
+            at(),
+        );
+        assert_eq!(
+            dest,
+            Path::new("/arc/2024/06/clinic/ward 2/memo_20240603_140509.ogg")
+        );
+    }
+
+    #[test]
+    fn archive_file_at_moves_into_the_dated_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("memo.mp3");
+        std::fs::write(&src, b"audio").unwrap();
+        let opts = opts(dir.path());
+        let dest = archive_file_at(&src, &opts, at()).unwrap().dest;
+        assert_eq!(
+            dest,
+            dir.path().join("archive/2024/06/memo_20240603_140509.mp3")
+        );
+        assert!(!src.exists());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"audio");
+    }
+
+    #[test]
+    fn archive_file_at_never_overwrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = opts(dir.path());
+        let src = dir.path().join("memo.mp3");
+        std::fs::write(&src, b"first").unwrap();
+        let first = archive_file_at(&src, &opts, at()).unwrap().dest;
+        std::fs::write(&src, b"second").unwrap();
+        let second = archive_file_at(&src, &opts, at()).unwrap().dest;
+        assert_eq!(
+            second,
+            dir.path()
+                .join("archive/2024/06/memo_20240603_140509 (1).mp3")
+        );
+        assert_eq!(std::fs::read(first).unwrap(), b"first");
+        assert_eq!(std::fs::read(second).unwrap(), b"second");
+    }
+
+    #[test]
+    fn archive_file_at_uses_the_given_clock() {
+        let dir = tempfile::tempdir().unwrap();
+        let inbox = dir.path().join("inbox");
+        std::fs::create_dir_all(inbox.join("ward 2")).unwrap();
+        let src = inbox.join("ward 2/memo.wav");
+        std::fs::write(&src, b"audio").unwrap();
+        let opts = ArchiveOptions {
+            source_root: Some(inbox),
+            ..opts(dir.path())
+        };
+        let new_years_eve = Local.with_ymd_and_hms(2023, 12, 31, 23, 59, 59).unwrap();
+        let dest = archive_file_at(&src, &opts, new_years_eve).unwrap().dest;
+        assert_eq!(
+            dest,
+            dir.path()
+                .join("archive/2023/12/ward 2/memo_20231231_235959.wav")
+        );
+    }
+
+    #[test]
+    fn archive_file_at_hashes_the_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("memo.mp3");
+        std::fs::write(&src, b"audio").unwrap();
+        let opts = ArchiveOptions {
+            hash_source: true,
+            ..opts(dir.path())
+        };
+        let archived = archive_file_at(&src, &opts, at()).unwrap();
+        assert_eq!(
+            archived.source_hash,
+            Some((
+                "6ed8919ce20490a5e3ad8630a4fab69475297abd07db73918dd5f36fcfaeb11b".to_owned(),
+                5
+            ))
+        );
+    }
+
+    #[test]
+    fn restore_archived_brings_the_markers_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = opts(dir.path());
+        let src = dir.path().join("memo.mp3");
+        std::fs::write(&src, b"audio").unwrap();
+        let dest = archive_file_at(&src, &opts, at()).unwrap().dest;
+        let exported = dest.with_extension("markers.txt");
+        std::fs::write(&exported, b"1\t00:01.000\n").unwrap();
+
+        let hash = restore_archived(&dest, &src, true).unwrap();
+        assert_eq!(hash.map(|(_, len)| len), Some(5));
+        assert_eq!(std::fs::read(&src).unwrap(), b"audio");
+        assert!(!dest.exists() && !exported.exists());
+        assert_eq!(
+            std::fs::read(dir.path().join("memo.markers.txt")).unwrap(),
+            b"1\t00:01.000\n"
+        );
+    }
+
+    #[test]
+    fn restore_archived_never_overwrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let (archived, original) = (dir.path().join("a.mp3"), dir.path().join("b.mp3"));
+        std::fs::write(&archived, b"old").unwrap();
+        std::fs::write(&original, b"new").unwrap();
+        assert!(restore_archived(&archived, &original, false).is_err());
+        assert_eq!(std::fs::read(&archived).unwrap(), b"old");
+        assert_eq!(std::fs::read(&original).unwrap(), b"new");
+    }
+
+    #[test]
+    fn unused_path_numbers_taken_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.v2.mp3");
+        assert_eq!(unused_path(path.clone()), path);
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(unused_path(path.clone()), dir.path().join("a.v2 (1).mp3"));
+        std::fs::write(dir.path().join("a.v2 (1).mp3"), b"").unwrap();
+        assert_eq!(unused_path(path), dir.path().join("a.v2 (2).mp3"));
+
+        let bare = dir.path().join("memo");
+        std::fs::write(&bare, b"").unwrap();
+        assert_eq!(unused_path(bare), dir.path().join("memo (1)"));
+    }
+
+    #[test]
+    fn unused_path_counts_dangling_symlinks_as_taken() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memo.wav");
+        std::os::unix::fs::symlink(dir.path().join("gone"), &path).unwrap();
+        assert_eq!(unused_path(path), dir.path().join("memo (1).wav"));
+    }
+
+    #[test]
+    fn source_subdir_relative_to_root() {
+        let root = Path::new("/in");
+        assert_eq!(
+            source_subdir(Path::new("/in/a/b/memo.mp3"), root),
+            Some(PathBuf::from("a/b"))
+        );
+        assert_eq!(source_subdir(Path::new("/in/memo.mp3"), root), None);
+        assert_eq!(source_subdir(Path::new("/elsewhere/memo.mp3"), root), None);
+    }
+
+    #[test]
+    fn source_subdir_through_a_symlinked_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real");
+        std::fs::create_dir_all(real.join("ward")).unwrap();
+        let alias = dir.path().join("alias");
+        std::os::unix::fs::symlink(&real, &alias).unwrap();
+        assert_eq!(
+            source_subdir(&real.join("ward/memo.mp3"), &alias),
+            Some(PathBuf::from("ward"))
+        );
+    }
+
+    #[test]
+    fn expand_home_only_touches_a_leading_tilde() {
+        let home = PathBuf::from(std::env::var_os("HOME").expect("HOME is set"));
+        assert_eq!(expand_home(Path::new("~/archive")), home.join("archive"));
+        assert_eq!(expand_home(Path::new("~")), home);
+        assert_eq!(
+            expand_home(Path::new("/srv/~/archive")),
+            Path::new("/srv/~/archive")
+        );
+        assert_eq!(expand_home(Path::new("~user/x")), Path::new("~user/x"));
+        assert_eq!(expand_home(Path::new("archive")), Path::new("archive"));
+    }
+}
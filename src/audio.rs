@@ -1,16 +1,34 @@
+use crate::config::{
+    ApplicationConfig, MonoMode, NormalizeMode, SampleStorage, MAX_SPEED, MIN_SPEED,
+};
+use crate::filter::VoiceFilter;
+use crate::stream::StreamStore;
+use crate::stretch::TimeStretch;
 use anyhow::{anyhow, Context, Result};
-use log::{error, info};
-use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
-use std::sync::Arc;
-use std::time::Duration;
+use log::{debug, error, info, warn};
+use rodio::cpal::traits::HostTrait;
+use rodio::queue::SourcesQueueOutput;
+use rodio::source::SineWave;
+use rodio::{DeviceTrait, OutputStream, OutputStreamHandle, Sink, Source};
+use rubato::{FftFixedIn, Resampler};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::{fs::File, path::Path};
-use symphonia::core::audio::SampleBuffer;
-use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::audio::{Channels, SampleBuffer};
+use symphonia::core::codecs::{
+    CodecParameters, DecoderOptions, CODEC_TYPE_AAC, CODEC_TYPE_NULL, CODEC_TYPE_PCM_F32BE,
+    CODEC_TYPE_PCM_F32BE_PLANAR, CODEC_TYPE_PCM_F32LE, CODEC_TYPE_PCM_F32LE_PLANAR,
+    CODEC_TYPE_PCM_F64BE, CODEC_TYPE_PCM_F64BE_PLANAR, CODEC_TYPE_PCM_F64LE,
+    CODEC_TYPE_PCM_F64LE_PLANAR,
+};
+use symphonia::core::conv::FromSample;
 use symphonia::core::errors::Error as SymphoniaError;
 use symphonia::core::formats::{FormatOptions, Track};
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::io::{MediaSourceStream, ReadOnlySource};
 use symphonia::core::meta::MetadataOptions;
-use symphonia::core::probe::Hint;
+use symphonia::core::probe::{Hint, ProbeResult};
+use symphonia::core::sample::SampleFormat;
 use symphonia::default::{get_codecs, get_probe};
 
 // Ensure the Opus plugin is linked and self-registers.
@@ -19,55 +37,369 @@ use symphonia_codec_opus as _;
 
 #[derive(Debug, Clone)]
 pub struct DecodedAudio {
-    pub samples: Arc<Vec<f32>>, // interleaved
+    pub samples: SampleStore, // interleaved
     pub sample_rate: u32,
-    pub channels: u16,        // 1 or 2
-    pub total_samples: usize, // interleaved count (frames * channels)
+    pub channels: u16,          // 1 or 2
+    pub total_samples: usize,   // interleaved count (frames * channels)
+    pub codec: &'static str,    // decoder short name, e.g. "mp3"
+    pub skipped_packets: usize, // undecodable packets that were dropped
+    pub source: SourceFormat,
+    pub norm_gain: f32, // playback gain from `DecodeOptions::normalize`
 }
 
-pub fn decode_to_f32_interleaved(path: &Path) -> Result<DecodedAudio> {
-    let f = File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
-    let mss = MediaSourceStream::new(Box::new(f), Default::default());
+/// The file's own format, before any downmix, mono fold or resampling.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceFormat {
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub bits_per_sample: Option<u32>,
+    /// Average over the file (size / duration); `None` for stdin.
+    pub bitrate_kbps: Option<u32>,
+}
+
+/// Interleaved decoded samples, shared with the playback source. `I16`
+/// halves memory at the cost of a conversion per sample on playback;
+/// `Streamed` decodes on demand for files above `stream_above_mb`.
+#[derive(Debug, Clone)]
+pub enum SampleStore {
+    F32(Arc<Vec<f32>>),
+    I16(Arc<Vec<i16>>),
+    Streamed(Arc<StreamStore>),
+}
+
+impl SampleStore {
+    pub fn len(&self) -> usize {
+        match self {
+            SampleStore::F32(v) => v.len(),
+            SampleStore::I16(v) => v.len(),
+            SampleStore::Streamed(s) => s.len(),
+        }
+    }
+
+    /// One sample; a streamed block that isn't decoded yet reads as
+    /// silence. Use a [`SampleReader`] for runs of samples.
+    #[inline]
+    pub fn get(&self, idx: usize) -> f32 {
+        match self {
+            SampleStore::F32(v) => v[idx],
+            SampleStore::I16(v) => f32::from_sample(v[idx]),
+            SampleStore::Streamed(s) => s
+                .cached(idx / s.block_len())
+                .and_then(|b| b.get(idx % s.block_len()).copied())
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+/// Reads a [`SampleStore`] by index, holding on to the current block of a
+/// streamed store so a run of reads costs one lookup per block. It never
+/// waits for the decoder.
+#[derive(Debug, Clone)]
+pub struct SampleReader {
+    data: SampleStore,
+    block: Option<(usize, Arc<Vec<f32>>)>, // (first index, samples)
+    // Move the stream's prefetch window along with the reads; for playback.
+    follow: bool,
+}
+
+impl SampleReader {
+    pub fn new(data: SampleStore) -> Self {
+        Self {
+            data,
+            block: None,
+            follow: false,
+        }
+    }
+
+    fn playback(data: SampleStore) -> Self {
+        Self {
+            follow: true,
+            ..Self::new(data)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Sample at `idx`, silence if its block isn't decoded yet.
+    #[inline]
+    pub fn get(&mut self, idx: usize) -> f32 {
+        self.try_get(idx).unwrap_or(0.0)
+    }
+
+    /// Sample at `idx`, `None` if its block isn't decoded yet.
+    #[inline]
+    pub fn try_get(&mut self, idx: usize) -> Option<f32> {
+        let store = match &self.data {
+            SampleStore::F32(v) => return Some(v[idx]),
+            SampleStore::I16(v) => return Some(f32::from_sample(v[idx])),
+            SampleStore::Streamed(s) => s,
+        };
+        let block_len = store.block_len();
+        let first = idx / block_len * block_len;
+        if self.block.as_ref().map(|(f, _)| *f) != Some(first) {
+            let n = idx / block_len;
+            let samples = if self.follow {
+                store.block(n)
+            } else {
+                store.cached(n)
+            }?;
+            self.block = Some((first, samples));
+        }
+        let (_, samples) = self.block.as_ref()?;
+        // A short block (end of file, failed decode) reads as silence.
+        Some(samples.get(idx - first).copied().unwrap_or(0.0))
+    }
+}
+
+/// Decode-time processing options, derived from the application config.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// Scale float sources whose peaks exceed full scale back into [-1, 1].
+    pub float_headroom: bool,
+    /// Fold sources with more than two channels down to stereo.
+    pub downmix: bool,
+    /// Sample type the decoded buffer is kept in.
+    pub store: SampleStorage,
+    /// Resample to `device_rate` after decoding.
+    pub resample_on_load: bool,
+    /// Output device rate; filled in by [`Player`] when loading.
+    pub device_rate: Option<u32>,
+    /// Average all channels into one, whatever the source layout. Used by
+    /// the one-off "Open as mono" retry.
+    pub force_mono: bool,
+    /// Measure a playback gain for this mode while still on the decode
+    /// thread, see [`DecodedAudio::norm_gain`].
+    pub normalize: NormalizeMode,
+    /// Refuse files whose decoded buffer would exceed this many bytes.
+    pub max_decode_bytes: Option<usize>,
+    /// Decode on demand instead of up front when the decoded buffer would
+    /// exceed this many bytes.
+    pub stream_above_bytes: Option<usize>,
+    /// Updated while decoding, for a progress display on another thread.
+    pub progress: Option<Arc<DecodeProgress>>,
+}
+
+/// Frames decoded so far and the expected total (0 if unknown).
+#[derive(Debug, Default)]
+pub struct DecodeProgress {
+    pub frames: AtomicU64,
+    pub total_frames: AtomicU64,
+}
+
+impl DecodeProgress {
+    /// 0..=1, or `None` while the total is unknown.
+    pub fn fraction(&self) -> Option<f32> {
+        let total = self.total_frames.load(Ordering::Relaxed);
+        (total > 0)
+            .then(|| (self.frames.load(Ordering::Relaxed) as f64 / total as f64).min(1.0) as f32)
+    }
+}
+
+/// The source has more channels than can be played without folding them.
+/// Kept as a distinct error so the UI can offer to reopen it as mono.
+#[derive(Debug)]
+pub struct UnsupportedChannels(pub usize);
+
+impl std::fmt::Display for UnsupportedChannels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unsupported channel count: {} (set application.downmix_surround = true to fold it down to stereo)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedChannels {}
+
+impl DecodeOptions {
+    pub fn from_config(app: &ApplicationConfig) -> Self {
+        Self {
+            float_headroom: app.float_headroom,
+            downmix: app.downmix_surround,
+            store: app.sample_store,
+            resample_on_load: app.resample_on_load,
+            device_rate: None,
+            force_mono: false,
+            normalize: app.normalize,
+            max_decode_bytes: (app.max_decode_mb > 0)
+                .then(|| (app.max_decode_mb as usize).saturating_mul(1024 * 1024)),
+            stream_above_bytes: (app.stream_above_mb > 0)
+                .then(|| (app.stream_above_mb as usize).saturating_mul(1024 * 1024)),
+            progress: None,
+        }
+    }
+}
+
+pub fn decode_to_f32_interleaved(path: &Path, opts: &DecodeOptions) -> Result<DecodedAudio> {
+    let open = || -> Result<MediaSourceStream> {
+        let f =
+            File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+        Ok(MediaSourceStream::new(Box::new(f), Default::default()))
+    };
+
+    // The extension helps with containerless/ambiguous streams, but it may
+    // be wrong (e.g. a WAV named .mp3), so fall back to a plain probe.
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = match probe(open()?, &hint) {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(
+                "{} ({}); retrying without the extension hint",
+                e,
+                path.display()
+            );
+            probe(open()?, &Hint::new())?
+        }
+    };
+    let mut audio = decode_probed(probed, opts)?;
+    let secs = audio.total_samples as f64 / audio.channels.max(1) as f64 / audio.sample_rate as f64;
+    if let (Ok(meta), true) = (std::fs::metadata(path), secs > 0.0) {
+        audio.source.bitrate_kbps = Some((meta.len() as f64 * 8.0 / secs / 1000.0).round() as u32);
+    }
+    Ok(audio)
+}
+
+/// Decode a whole stream piped on stdin. The input is not seekable, so it is
+/// fully buffered in memory before playback; formats that need to seek
+/// while probing may fail here even though they open fine from a file.
+pub fn decode_stdin_to_f32_interleaved(opts: &DecodeOptions) -> Result<DecodedAudio> {
+    let src = ReadOnlySource::new(std::io::stdin());
+    let mss = MediaSourceStream::new(Box::new(src), Default::default());
+    // Streaming needs to seek back into the source.
+    let opts = DecodeOptions {
+        stream_above_bytes: None,
+        ..opts.clone()
+    };
+    decode_probed(probe(mss, &Hint::new())?, &opts)
+}
 
-    let hint = Hint::new();
-    let probed = get_probe()
+fn probe(mss: MediaSourceStream, hint: &Hint) -> Result<ProbeResult> {
+    get_probe()
         .format(
-            &hint,
+            hint,
             mss,
             &FormatOptions::default(),
             &MetadataOptions::default(),
         )
-        .map_err(|e| anyhow!("Probe failed: {e}"))?;
+        .map_err(|e| anyhow!("Probe failed: {e}"))
+}
 
+fn decode_probed(probed: ProbeResult, opts: &DecodeOptions) -> Result<DecodedAudio> {
     let mut format = probed.format;
 
     // choose best track
     let track = select_best_track(format.tracks())
         .ok_or_else(|| anyhow!("No supported audio track found"))?;
     let codec_params = track.codec_params.clone(); // Clone to avoid borrow issues
+    let track_id = track.id;
 
     let mut decoder = get_codecs()
         .make(&codec_params, &DecoderOptions::default())
         .map_err(|e| anyhow!("Decoder creation failed: {e}"))?;
 
-    let sample_rate = codec_params
+    let mut sample_rate = codec_params
         .sample_rate
         .ok_or_else(|| anyhow!("Missing sample rate"))?;
     let channels = codec_params
         .channels
+        .or_else(|| aac_config_channels(&codec_params))
         .ok_or_else(|| anyhow!("Missing channel info"))?;
     let ch_count = channels.count();
     if ch_count == 0 {
         return Err(anyhow!("Zero channels"));
     }
-    if ch_count > 2 {
-        return Err(anyhow!(
-            "Unsupported channel count: {} (only mono/stereo supported in Phase 1)",
-            ch_count
-        ));
+    if ch_count > 2 && !opts.downmix && !opts.force_mono {
+        return Err(UnsupportedChannels(ch_count).into());
+    }
+    let to_mono = opts.force_mono && ch_count > 1;
+
+    // Without float headroom, downmix or resampling there is nothing to do
+    // on the whole f32 buffer, so i16 storage can be filled packet by packet.
+    let resample_to = opts
+        .device_rate
+        .filter(|&rate| opts.resample_on_load && rate != sample_rate);
+    let needs_f32_pass = (opts.float_headroom && is_float_source(&codec_params))
+        || ch_count > 2
+        || to_mono
+        || resample_to.is_some();
+    let direct_i16 = opts.store == SampleStorage::I16 && !needs_f32_pass;
+
+    let codec = get_codecs()
+        .get_codec(codec_params.codec)
+        .map(|d| d.short_name)
+        .unwrap_or("unknown");
+    let source = SourceFormat {
+        sample_rate,
+        channels: ch_count,
+        bits_per_sample: codec_params.bits_per_sample,
+        bitrate_kbps: None,
+    };
+
+    // Check the probed length up front so a huge file fails (or streams)
+    // before allocating anything; the running check below covers streams
+    // that don't report their length.
+    let bytes_per_sample = if direct_i16 { 2 } else { 4 };
+    if let Some(frames) = codec_params.n_frames {
+        let estimate = (frames as usize)
+            .saturating_mul(ch_count)
+            .saturating_mul(bytes_per_sample);
+        // Streamed blocks go straight to playback, so only files that need
+        // no whole-buffer processing and whose timestamps count frames
+        // qualify.
+        let frame_timestamps = codec_params
+            .time_base
+            .is_some_and(|tb| tb.numer == 1 && tb.denom == sample_rate);
+        if opts
+            .stream_above_bytes
+            .is_some_and(|limit| estimate > limit)
+            && !needs_f32_pass
+            && frame_timestamps
+        {
+            info!(
+                "Streaming: sr={} Hz, ch={}, frames={} (~{} MB decoded)",
+                sample_rate,
+                ch_count,
+                frames,
+                estimate / (1024 * 1024)
+            );
+            let store = StreamStore::new(
+                format,
+                decoder,
+                track_id,
+                sample_rate,
+                ch_count,
+                frames as usize,
+            )
+            .context("Failed to start the stream decoder")?;
+            return Ok(DecodedAudio {
+                total_samples: store.len(),
+                samples: SampleStore::Streamed(Arc::new(store)),
+                sample_rate,
+                channels: ch_count as u16,
+                codec,
+                skipped_packets: 0,
+                source,
+                norm_gain: 1.0,
+            });
+        }
+        if let Some(limit) = opts.max_decode_bytes.filter(|&limit| estimate > limit) {
+            return Err(too_large(estimate, limit));
+        }
+    }
+
+    let mut skipped_packets = 0;
+    if let (Some(progress), Some(frames)) = (&opts.progress, codec_params.n_frames) {
+        progress.total_frames.store(frames, Ordering::Relaxed);
     }
 
     let mut samples: Vec<f32> = Vec::new();
+    let mut samples_i16: Vec<i16> = Vec::new();
     let mut sample_buf: Option<SampleBuffer<f32>> = None;
 
     loop {
@@ -94,6 +426,7 @@ pub fn decode_to_f32_interleaved(path: &Path) -> Result<DecodedAudio> {
             Err(SymphoniaError::DecodeError(e)) => {
                 // Recoverable: skip bad packet.
                 error!("Decode error (skipping packet): {e}");
+                skipped_packets += 1;
                 continue;
             }
             Err(e) => return Err(anyhow!("Decode error: {e}")),
@@ -112,26 +445,309 @@ pub fn decode_to_f32_interleaved(path: &Path) -> Result<DecodedAudio> {
         let sbuf = sample_buf.as_mut().unwrap();
         sbuf.copy_interleaved_ref(decoded);
 
-        samples.extend_from_slice(sbuf.samples());
+        if direct_i16 {
+            samples_i16.extend(sbuf.samples().iter().map(|&v| i16::from_sample(v)));
+        } else {
+            samples.extend_from_slice(sbuf.samples());
+        }
+        if let Some(progress) = &opts.progress {
+            let frames = (samples.len() + samples_i16.len()) / ch_count;
+            progress.frames.store(frames as u64, Ordering::Relaxed);
+        }
+        if let Some(limit) = opts.max_decode_bytes {
+            let used = (samples.len() + samples_i16.len()) * bytes_per_sample;
+            if used > limit {
+                return Err(too_large(used, limit));
+            }
+        }
+    }
+
+    if opts.float_headroom && is_float_source(&codec_params) {
+        apply_float_headroom(&mut samples);
     }
 
+    let mut out_channels = ch_count;
+    if to_mono {
+        samples = fold_to_mono(&samples, ch_count);
+        out_channels = 1;
+        info!("Folded {} channels to mono", ch_count);
+    } else if ch_count > 2 {
+        samples = downmix_to_stereo(&samples, channels);
+        out_channels = 2;
+        info!("Downmixed {} channels to stereo", ch_count);
+    }
+
+    if let Some(rate) = resample_to {
+        let started = Instant::now();
+        samples = resample_interleaved(&samples, out_channels, sample_rate, rate)?;
+        info!(
+            "Resampled {} Hz -> {} Hz in {:.2?}",
+            sample_rate,
+            rate,
+            started.elapsed()
+        );
+        sample_rate = rate;
+    }
+
+    let samples = match opts.store {
+        SampleStorage::F32 => SampleStore::F32(Arc::new(samples)),
+        SampleStorage::I16 if direct_i16 => SampleStore::I16(Arc::new(samples_i16)),
+        SampleStorage::I16 => SampleStore::I16(Arc::new(
+            samples.into_iter().map(i16::from_sample).collect(),
+        )),
+    };
+
     let total_samples = samples.len();
+    let norm_gain = normalization_gain(&samples, opts.normalize);
     info!(
-        "Decoded: sr={} Hz, ch={}, frames={}, seconds≈{:.3}",
+        "Decoded: sr={} Hz, ch={}, frames={}, seconds≈{:.3}, store={:?}",
         sample_rate,
-        ch_count,
-        total_samples / ch_count,
-        (total_samples as f64) / (sample_rate as f64) / (ch_count as f64)
+        out_channels,
+        total_samples / out_channels,
+        (total_samples as f64) / (sample_rate as f64) / (out_channels as f64),
+        opts.store
     );
 
     Ok(DecodedAudio {
-        samples: Arc::new(samples),
+        samples,
         sample_rate,
-        channels: ch_count as u16,
+        channels: out_channels as u16,
         total_samples,
+        codec,
+        skipped_packets,
+        source,
+        norm_gain,
     })
 }
 
+// Normalization targets and limits (linear).
+const NORM_PEAK_TARGET: f32 = 0.89; // -1 dBFS
+const NORM_RMS_TARGET: f32 = 0.1; // -20 dBFS
+const NORM_MAX_GAIN: f32 = 15.85; // +24 dB; don't drag up near-silent files
+
+/// Playback gain for `mode`, never pushing the peak past
+/// `NORM_PEAK_TARGET`. Streamed files would have to be decoded in full to
+/// measure, so they get no normalization.
+fn normalization_gain(samples: &SampleStore, mode: NormalizeMode) -> f32 {
+    if mode == NormalizeMode::Off || matches!(samples, SampleStore::Streamed(_)) {
+        return 1.0;
+    }
+    let (mut peak, mut sum_sq) = (0.0_f32, 0.0_f64);
+    for i in 0..samples.len() {
+        let v = samples.get(i);
+        peak = peak.max(v.abs());
+        sum_sq += (v * v) as f64;
+    }
+    if peak <= 0.0 {
+        return 1.0;
+    }
+    let peak_gain = NORM_PEAK_TARGET / peak;
+    let gain = match mode {
+        NormalizeMode::Rms => {
+            let rms = (sum_sq / samples.len() as f64).sqrt() as f32;
+            (NORM_RMS_TARGET / rms).min(peak_gain)
+        }
+        _ => peak_gain,
+    };
+    let gain = gain.min(NORM_MAX_GAIN);
+    info!("Normalization ({:?}): {:+.1} dB", mode, 20.0 * gain.log10());
+    gain
+}
+
+fn too_large(bytes: usize, limit: usize) -> anyhow::Error {
+    anyhow!(
+        "File too large to decode: needs about {} MB, limit is {} MB (set application.stream_above_mb below that to stream it, or raise application.max_decode_mb)",
+        bytes / (1024 * 1024),
+        limit / (1024 * 1024)
+    )
+}
+
+fn is_float_source(params: &CodecParameters) -> bool {
+    matches!(
+        params.sample_format,
+        Some(SampleFormat::F32) | Some(SampleFormat::F64)
+    ) || [
+        CODEC_TYPE_PCM_F32LE,
+        CODEC_TYPE_PCM_F32LE_PLANAR,
+        CODEC_TYPE_PCM_F32BE,
+        CODEC_TYPE_PCM_F32BE_PLANAR,
+        CODEC_TYPE_PCM_F64LE,
+        CODEC_TYPE_PCM_F64LE_PLANAR,
+        CODEC_TYPE_PCM_F64BE,
+        CODEC_TYPE_PCM_F64BE_PLANAR,
+    ]
+    .contains(&params.codec)
+}
+
+/// The MP4 reader leaves `channels` unset for AAC; the count is in the
+/// AudioSpecificConfig's channelConfiguration (after 5 bits of object type
+/// and 4 of sample-rate index). Only the plain configurations 1-6 map to
+/// a layout; anything else still fails as missing channel info.
+fn aac_config_channels(params: &CodecParameters) -> Option<Channels> {
+    if params.codec != CODEC_TYPE_AAC {
+        return None;
+    }
+    let asc = params.extra_data.as_deref()?;
+    if asc.len() < 2 || asc[0] >> 3 == 31 || (asc[0] & 0x07) << 1 | asc[1] >> 7 == 15 {
+        // Escaped object type or explicit sample rate shift the field.
+        return None;
+    }
+    let front = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+    match (asc[1] >> 3) & 0x0f {
+        1 => Some(Channels::FRONT_LEFT),
+        2 => Some(front),
+        3 => Some(front | Channels::FRONT_CENTRE),
+        4 => Some(front | Channels::FRONT_CENTRE | Channels::REAR_CENTRE),
+        5 => Some(front | Channels::FRONT_CENTRE | Channels::REAR_LEFT | Channels::REAR_RIGHT),
+        6 => Some(
+            front
+                | Channels::FRONT_CENTRE
+                | Channels::REAR_LEFT
+                | Channels::REAR_RIGHT
+                | Channels::LFE1,
+        ),
+        _ => None,
+    }
+}
+
+/// Float PCM may carry peaks above full scale, which the output stage would
+/// hard-clip. Scale the whole buffer down so the peak sits at 1.0.
+fn apply_float_headroom(samples: &mut [f32]) {
+    if let Some(peak) = limit_peak(samples) {
+        info!(
+            "Float source peaks at {:.3} (+{:.2} dBFS); applied {:.2} dB headroom",
+            peak,
+            20.0 * peak.log10(),
+            -20.0 * peak.log10()
+        );
+    }
+}
+
+/// If any sample exceeds full scale, scale the buffer so the peak is 1.0.
+/// Returns the original peak when scaling was applied.
+fn limit_peak(samples: &mut [f32]) -> Option<f32> {
+    let peak = samples.iter().fold(0.0_f32, |m, v| m.max(v.abs()));
+    if peak <= 1.0 {
+        return None;
+    }
+    let gain = 1.0 / peak;
+    for v in samples.iter_mut() {
+        *v *= gain;
+    }
+    Some(peak)
+}
+
+/// Left/right gains for one source channel when folding to stereo:
+/// left/right-side channels go to their side, centres to both at -3 dB,
+/// LFE is dropped.
+fn stereo_fold_gains(ch: Channels) -> (f32, f32) {
+    let left = Channels::FRONT_LEFT
+        | Channels::REAR_LEFT
+        | Channels::FRONT_LEFT_CENTRE
+        | Channels::SIDE_LEFT
+        | Channels::TOP_FRONT_LEFT
+        | Channels::TOP_REAR_LEFT
+        | Channels::REAR_LEFT_CENTRE
+        | Channels::FRONT_LEFT_WIDE
+        | Channels::FRONT_LEFT_HIGH;
+    let right = Channels::FRONT_RIGHT
+        | Channels::REAR_RIGHT
+        | Channels::FRONT_RIGHT_CENTRE
+        | Channels::SIDE_RIGHT
+        | Channels::TOP_FRONT_RIGHT
+        | Channels::TOP_REAR_RIGHT
+        | Channels::REAR_RIGHT_CENTRE
+        | Channels::FRONT_RIGHT_WIDE
+        | Channels::FRONT_RIGHT_HIGH;
+    let lfe = Channels::LFE1 | Channels::LFE2;
+    if left.contains(ch) {
+        (1.0, 0.0)
+    } else if right.contains(ch) {
+        (0.0, 1.0)
+    } else if lfe.contains(ch) {
+        (0.0, 0.0)
+    } else {
+        (
+            std::f32::consts::FRAC_1_SQRT_2,
+            std::f32::consts::FRAC_1_SQRT_2,
+        )
+    }
+}
+
+/// Fold interleaved multichannel samples (ordered as in `channels`) to
+/// interleaved stereo, limiting the result to full scale.
+fn downmix_to_stereo(samples: &[f32], channels: Channels) -> Vec<f32> {
+    let gains: Vec<(f32, f32)> = channels.iter().map(stereo_fold_gains).collect();
+    let ch = gains.len();
+    let mut out = Vec::with_capacity(samples.len() / ch * 2);
+    for frame in samples.chunks_exact(ch) {
+        let (mut l, mut r) = (0.0_f32, 0.0_f32);
+        for (v, (gl, gr)) in frame.iter().zip(&gains) {
+            l += v * gl;
+            r += v * gr;
+        }
+        out.push(l);
+        out.push(r);
+    }
+    limit_peak(&mut out);
+    out
+}
+
+/// Average each interleaved frame of `ch` channels into one sample.
+fn fold_to_mono(samples: &[f32], ch: usize) -> Vec<f32> {
+    samples
+        .chunks_exact(ch)
+        .map(|frame| frame.iter().sum::<f32>() / ch as f32)
+        .collect()
+}
+
+/// Resample interleaved audio from `from` to `to` Hz. The output has the
+/// same duration as the input; the resampler's delay is trimmed off.
+fn resample_interleaved(samples: &[f32], channels: usize, from: u32, to: u32) -> Result<Vec<f32>> {
+    const CHUNK_FRAMES: usize = 4096;
+    let mut resampler =
+        FftFixedIn::<f32>::new(from as usize, to as usize, CHUNK_FRAMES, 2, channels)
+            .map_err(|e| anyhow!("Resampler setup failed: {e}"))?;
+
+    let frames = samples.len() / channels;
+    let planar: Vec<Vec<f32>> = (0..channels)
+        .map(|c| samples.iter().skip(c).step_by(channels).copied().collect())
+        .collect();
+    let delay = resampler.output_delay();
+    let expected = (frames as u64 * to as u64 / from as u64) as usize;
+    let mut out: Vec<Vec<f32>> =
+        vec![Vec::with_capacity(delay + expected + CHUNK_FRAMES); channels];
+
+    let mut pos = 0;
+    while out[0].len() < delay + expected {
+        let need = resampler.input_frames_next();
+        let chunk = if pos + need <= frames {
+            let input: Vec<&[f32]> = planar.iter().map(|c| &c[pos..pos + need]).collect();
+            resampler.process(&input, None)
+        } else if pos < frames {
+            // Zero-padded tail
+            let input: Vec<&[f32]> = planar.iter().map(|c| &c[pos..]).collect();
+            resampler.process_partial(Some(&input), None)
+        } else {
+            // Flush the resampler's delay line
+            resampler.process_partial(None::<&[&[f32]]>, None)
+        }
+        .map_err(|e| anyhow!("Resampling failed: {e}"))?;
+        pos += need;
+        for (dst, src) in out.iter_mut().zip(chunk) {
+            dst.extend_from_slice(&src);
+        }
+    }
+
+    let mut interleaved = Vec::with_capacity(expected * channels);
+    for i in delay..delay + expected {
+        for ch in &out {
+            interleaved.push(ch[i]);
+        }
+    }
+    Ok(interleaved)
+}
+
 fn select_best_track(tracks: &[Track]) -> Option<&Track> {
     // Pick the first track with a known codec type (not NULL).
     tracks
@@ -140,42 +756,403 @@ fn select_best_track(tracks: &[Track]) -> Option<&Track> {
 }
 
 pub struct Output {
-    pub _stream: OutputStream,
-    pub handle: OutputStreamHandle,
+    // `None` for an idle output, whose sinks play into `idle_queue`.
+    stream: Option<(OutputStream, OutputStreamHandle)>,
+    // Where the newest sink of an idle output plays to, for pulling from.
+    idle_queue: Mutex<Option<SourcesQueueOutput<f32>>>,
+    /// Stream rate, when the default device opened with its default config.
+    pub sample_rate: Option<u32>,
 }
 
 impl Output {
-    pub fn new() -> Result<Self> {
+    /// Open the output device called `device`, or the default one when
+    /// unset or no device has that name.
+    pub fn new(device: Option<&str>) -> Result<Self> {
+        let host = rodio::cpal::default_host();
+        let named = device.and_then(|name| {
+            let found = host
+                .output_devices()
+                .ok()?
+                .find(|d| d.name().map(|n| n == name).unwrap_or(false));
+            if found.is_none() {
+                warn!("Output device {:?} not found; using the default", name);
+            }
+            found
+        });
+        let named_requested = named.is_some();
+        if let Some(device) = named.or_else(|| host.default_output_device()) {
+            if let Ok(config) = device.default_output_config() {
+                let rate = config.sample_rate().0;
+                if let Ok((_stream, handle)) = OutputStream::try_from_device_config(&device, config)
+                {
+                    if named_requested {
+                        info!("Audio output: {}", device.name().unwrap_or_default());
+                    }
+                    return Ok(Self {
+                        stream: Some((_stream, handle)),
+                        idle_queue: Mutex::new(None),
+                        sample_rate: Some(rate),
+                    });
+                }
+            }
+            if named_requested {
+                warn!("Opening the selected output device failed; using the default");
+            }
+        }
+        // Let rodio fall back to any device that works.
         let (_stream, handle) = OutputStream::try_default()?;
-        Ok(Self { _stream, handle })
+        Ok(Self {
+            stream: Some((_stream, handle)),
+            idle_queue: Mutex::new(None),
+            sample_rate: None,
+        })
+    }
+
+    /// An output without a device: nothing plays unless pulled through
+    /// [`Self::pull`].
+    #[cfg(test)]
+    pub fn idle() -> Self {
+        Self {
+            stream: None,
+            idle_queue: Mutex::new(None),
+            sample_rate: None,
+        }
+    }
+
+    fn new_sink(&self) -> Sink {
+        match &self.stream {
+            Some((_, handle)) => Sink::try_new(handle).expect("Failed to create Sink"),
+            None => {
+                let (sink, queue) = Sink::new_idle();
+                *self.idle_queue.lock().unwrap() = Some(queue);
+                sink
+            }
+        }
+    }
+
+    /// Play `n` samples of an idle output's newest sink, as a device would.
+    #[cfg(test)]
+    pub fn pull(&self, n: usize) -> Vec<f32> {
+        let mut queue = self.idle_queue.lock().unwrap();
+        match queue.as_mut() {
+            Some(queue) => queue.take(n).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Names of the available output devices, for choosing one.
+pub fn output_device_names() -> Vec<String> {
+    match rodio::cpal::default_host().output_devices() {
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+        Err(e) => {
+            warn!("Listing output devices failed: {}", e);
+            Vec::new()
+        }
     }
 }
 
+/// Everything besides the start position that shapes a sink's source; a
+/// paused sink is only resumed if none of it changed meanwhile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SinkSettings {
+    speed: f32,
+    loop_region: Option<(usize, usize)>,
+    force_mono_output: bool,
+    mono_output: MonoMode,
+    preserve_pitch: bool,
+    voice_filter: bool,
+    highpass_hz: f32,
+    presence_boost: bool,
+    sample_accurate: bool,
+    speed_ramp_ms: u64,
+    fade_ms: u64,
+}
+
 pub struct SliceSource {
-    data: Arc<Vec<f32>>,
+    data: SampleReader,
     pos: usize, // interleaved index
     end: usize, // interleaved index
     channels: u16,
     sample_rate: u32, // adjusted for playback speed
+    // Collapse each frame to one sample and report a single channel.
+    mono_out: bool,
+    // Spread the collapsed sample over a stereo pair instead; `held` is
+    // the right-hand sample still to be returned.
+    route: MonoMode,
+    held: Option<f32>,
+    // When set, playback wraps from `end` back to here instead of stopping,
+    // `wraps_left` more times if limited, then carries on past `end`.
+    loop_start: Option<usize>,
+    wraps_left: Option<usize>,
+    // Live speed control; the rate is re-read every RAMP_CHUNK_FRAMES.
+    ramp: Option<SpeedRamp>,
+    // High-pass/presence filter, per output channel.
+    filter: Option<VoiceFilter>,
+}
+
+/// What a source with fades does next, set by the player while it plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FadeMode {
+    Play,
+    /// Fade out, then pause in place (silence, not advancing) until the
+    /// mode goes back to `Play`, which fades in again.
+    Hold,
+    /// Fade out, then end.
+    Stop,
+}
+
+/// A [`FadeMode`] shared between the player and its source.
+#[derive(Debug)]
+pub struct FadeControl(AtomicU8);
+
+impl FadeControl {
+    pub fn new(mode: FadeMode) -> Self {
+        Self(AtomicU8::new(mode as u8))
+    }
+
+    pub fn set(&self, mode: FadeMode) {
+        self.0.store(mode as u8, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> FadeMode {
+        match self.0.load(Ordering::Relaxed) {
+            0 => FadeMode::Play,
+            1 => FadeMode::Hold,
+            _ => FadeMode::Stop,
+        }
+    }
+}
+
+/// Linear fades against clicks when a source starts, pauses or stops.
+/// The level moves one step per output sample towards full or zero, so a
+/// quick change of mind turns around wherever the fade has got to.
+struct Fade {
+    len: usize,   // output samples for a full fade
+    level: usize, // 0..=len
+    control: Arc<FadeControl>,
+}
+
+impl Fade {
+    /// Gain for the next sample; `None` once a stop has faded out.
+    fn gain(&mut self) -> Option<f32> {
+        let mode = self.control.get();
+        if mode == FadeMode::Play {
+            self.level = (self.level + 1).min(self.len);
+        } else if self.level == 0 {
+            if mode == FadeMode::Stop {
+                return None;
+            }
+        } else {
+            self.level -= 1;
+        }
+        Some(self.level as f32 / self.len as f32)
+    }
+
+    /// Faded out and asked to hold.
+    fn holding(&self) -> bool {
+        self.level == 0 && self.control.get() == FadeMode::Hold
+    }
+}
+
+// Skip-silence analysis window and how much quiet to keep before speech.
+const SILENCE_WINDOW_MS: usize = 20;
+const SILENCE_LEAD_MS: usize = 150;
+
+// Audio run through a new source's filter before its start position.
+const FILTER_WARMUP_FRAMES: usize = 2048;
+
+// Output frames between rate updates while a speed ramp is possible.
+const RAMP_CHUNK_FRAMES: usize = 1024;
+
+// How long starting playback of a streamed file waits for the decoder to
+// reach the start position before opening on silence.
+const STREAM_START_WAIT: Duration = Duration::from_millis(500);
+
+/// Glides the reported sample rate towards a shared target speed. rodio
+/// re-reads `sample_rate()` at every frame boundary, so the source reports
+/// fixed-length frames while this is active.
+struct SpeedRamp {
+    target: Arc<AtomicU32>, // f32 bits
+    duration: Duration,
+    base_rate: u32,
+    from: f32,
+    current: f32,
+    last_target: f32,
+    started: Instant,
+    emitted: usize, // output samples since the last rate update
+}
+
+impl SpeedRamp {
+    fn update(&mut self) -> u32 {
+        let target = f32::from_bits(self.target.load(Ordering::Relaxed));
+        if target != self.last_target {
+            self.from = self.current;
+            self.last_target = target;
+            self.started = Instant::now();
+        }
+        let t = self.started.elapsed().as_secs_f32() / self.duration.as_secs_f32().max(1e-3);
+        self.current = self.from + (target - self.from) * t.min(1.0);
+        ((self.base_rate as f32) * self.current).round().max(1.0) as u32
+    }
 }
 
 impl SliceSource {
     pub fn new(
-        data: Arc<Vec<f32>>,
+        data: SampleStore,
         start: usize,
         channels: u16,
         base_sample_rate: u32,
         speed: f32,
+        mono_out: bool,
     ) -> Self {
         let start = start.min(data.len());
         let end = data.len();
         let adj_sr = ((base_sample_rate as f32) * speed).round().max(1.0) as u32;
         Self {
-            data,
+            data: SampleReader::playback(data),
             pos: start,
             end,
             channels,
             sample_rate: adj_sr,
+            mono_out: mono_out && channels > 1,
+            route: MonoMode::Off,
+            held: None,
+            loop_start: None,
+            wraps_left: None,
+            ramp: None,
+            filter: None,
+        }
+    }
+
+    /// Follow `speed` (f32 bits) while playing, gliding to each new value
+    /// over `duration` instead of needing a rebuild.
+    pub fn with_speed_control(
+        mut self,
+        base_sample_rate: u32,
+        speed: Arc<AtomicU32>,
+        duration: Duration,
+    ) -> Self {
+        let current = f32::from_bits(speed.load(Ordering::Relaxed));
+        self.ramp = Some(SpeedRamp {
+            target: speed,
+            duration,
+            base_rate: base_sample_rate,
+            from: current,
+            current,
+            last_target: current,
+            started: Instant::now(),
+            emitted: 0,
+        });
+        self
+    }
+
+    /// Play a mono mix on one or both channels of a stereo pair, see
+    /// [`MonoMode`]. Call before [`Self::with_filter`].
+    pub fn with_mono_output(mut self, mode: MonoMode) -> Self {
+        if mode != MonoMode::Off {
+            self.mono_out = true;
+            self.route = mode;
+        }
+        self
+    }
+
+    fn ramp_chunk_len(&self) -> usize {
+        RAMP_CHUNK_FRAMES * self.out_channels() as usize
+    }
+
+    /// Run output through `filter`. It is primed on the audio just before
+    /// the start so a rebuilt source picks up where the old one left off
+    /// instead of starting from a cold (clicking) filter state.
+    pub fn with_filter(mut self, mut filter: VoiceFilter) -> Self {
+        let ch = self.channels as usize;
+        let warm = FILTER_WARMUP_FRAMES * ch;
+        let from = self.pos.saturating_sub(warm) / ch * ch;
+        for frame in (from..self.pos).step_by(ch) {
+            if self.mono_out {
+                let end = (frame + ch).min(self.pos);
+                let sum: f32 = (frame..end).map(|i| self.data.get(i)).sum();
+                filter.process(0, sum / ch as f32);
+            } else {
+                for c in 0..ch.min(self.pos - frame) {
+                    filter.process(c, self.data.get(frame + c));
+                }
+            }
+        }
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Repeat `[start, end)` (interleaved indices), going back to `start`
+    /// `wraps` times and then playing on, or indefinitely for `None`.
+    pub fn with_loop(mut self, start: usize, end: usize, wraps: Option<usize>) -> Self {
+        let end = end.min(self.data.len());
+        if start < end {
+            self.end = end;
+            self.loop_start = Some(start);
+            self.wraps_left = wraps;
+            if self.pos >= end || self.pos < start {
+                self.pos = start;
+            }
+        }
+        self
+    }
+
+    /// The next sample, or with `mono_out` the next frame's mix, through
+    /// the filter.
+    fn read_filtered(&mut self) -> f32 {
+        let channel = if self.mono_out {
+            0
+        } else {
+            self.pos % self.channels as usize
+        };
+        let v = if self.mono_out {
+            let ch = self.channels as usize;
+            let frame_end = (self.pos + ch).min(self.end);
+            let sum: f32 = (self.pos..frame_end).map(|i| self.data.get(i)).sum();
+            self.pos = frame_end;
+            sum / ch as f32
+        } else {
+            let v = self.data.get(self.pos);
+            self.pos += 1;
+            v
+        };
+        match &mut self.filter {
+            Some(filter) => filter.process(channel, v),
+            None => v,
+        }
+    }
+
+    /// At `end`: back to the loop start while wraps are left, else on
+    /// to the end of the data. `None` once there is nothing left to play.
+    fn wrap(&mut self) -> Option<()> {
+        let start = self.loop_start?;
+        match &mut self.wraps_left {
+            Some(0) => {
+                self.loop_start = None;
+                self.end = self.data.len();
+                (self.pos < self.end).then_some(())
+            }
+            Some(n) => {
+                *n -= 1;
+                self.pos = start;
+                Some(())
+            }
+            None => {
+                self.pos = start;
+                Some(())
+            }
+        }
+    }
+
+    fn out_channels(&self) -> u16 {
+        if self.route != MonoMode::Off {
+            2
+        } else if self.mono_out {
+            1
+        } else {
+            self.channels
         }
     }
 }
@@ -184,27 +1161,78 @@ impl Iterator for SliceSource {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.end {
-            return None;
+        let v = match self.held.take() {
+            Some(v) => v,
+            None => {
+                if self.pos >= self.end {
+                    self.wrap()?;
+                }
+                let v = self.read_filtered();
+                match self.route {
+                    MonoMode::Off => v,
+                    MonoMode::LeftEar => {
+                        self.held = Some(0.0);
+                        v
+                    }
+                    MonoMode::RightEar => {
+                        self.held = Some(v);
+                        0.0
+                    }
+                    MonoMode::Sum => {
+                        self.held = Some(v);
+                        v
+                    }
+                }
+            }
+        };
+        let chunk = self.ramp_chunk_len();
+        if let Some(ramp) = &mut self.ramp {
+            ramp.emitted += 1;
+            if ramp.emitted >= chunk {
+                // Frame boundary: rodio picks up the new rate before the next sample.
+                ramp.emitted = 0;
+                self.sample_rate = ramp.update();
+            }
         }
-        let v = self.data[self.pos];
-        self.pos += 1;
         Some(v)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.loop_start.is_some() && self.wraps_left.is_none() {
+            return (usize::MAX, None);
+        }
         let rem = self.end.saturating_sub(self.pos);
-        (rem, Some(rem))
+        let rem = if self.mono_out {
+            rem.div_ceil(self.channels as usize)
+        } else {
+            rem
+        };
+        let rem = if self.route != MonoMode::Off {
+            rem * 2 + self.held.is_some() as usize
+        } else {
+            rem
+        };
+        // A counted loop has at least the rest of this pass to go.
+        (rem, self.loop_start.is_none().then_some(rem))
     }
 }
 
 impl Source for SliceSource {
     fn current_frame_len(&self) -> Option<usize> {
-        None
+        let ramp = self.ramp.as_ref()?;
+        let in_chunk = self.ramp_chunk_len() - ramp.emitted;
+        if self.loop_start.is_some() {
+            return Some(in_chunk);
+        }
+        let remaining = self.size_hint().0;
+        Some(in_chunk.min(remaining))
     }
 
+    /// With `mono_out`, rodio maps the single channel onto every device
+    /// channel (both ears on stereo, one channel on a mono device). A
+    /// routed mix is always a stereo pair.
     fn channels(&self) -> u16 {
-        self.channels
+        self.out_channels()
     }
 
     fn sample_rate(&self) -> u32 {
@@ -212,9 +1240,10 @@ impl Source for SliceSource {
     }
 
     fn total_duration(&self) -> Option<Duration> {
-        if self.channels == 0 {
+        if self.channels == 0 || self.loop_start.is_some() {
             return None;
         }
+        // Source frames, so the same whatever the output channel count.
         let frames = (self.end.saturating_sub(self.pos)) as u64 / (self.channels as u64);
         Some(Duration::from_secs_f64(
             frames as f64 / (self.sample_rate as f64),
@@ -222,53 +1251,391 @@ impl Source for SliceSource {
     }
 }
 
-pub struct Player {
-    pub output: Output,
-    pub sink: Option<Sink>,
+/// Counts the samples `inner` has handed to the mixer, for sample-accurate
+/// position tracking; [`PlayCounter`] turns the count into a position.
+pub struct CountingSource<S> {
+    inner: S,
+    yielded: usize,
+    count: Arc<AtomicUsize>, // shared copy of `yielded`
+}
 
-    pub audio: Option<DecodedAudio>,
-    pub file_path: Option<std::path::PathBuf>,
+impl<S> CountingSource<S> {
+    pub fn new(inner: S, count: Arc<AtomicUsize>) -> Self {
+        count.store(0, Ordering::Relaxed);
+        Self {
+            inner,
+            yielded: 0,
+            count,
+        }
+    }
+}
 
-    pub playing: bool,
-    pub speed: f32, // 0.75, 1.0, 1.25, 1.5
+impl<S: Source<Item = f32>> Iterator for CountingSource<S> {
+    type Item = f32;
 
-    // playback position management
-    pub content_index: usize, // interleaved index when paused, or last seeked
-    pub play_start_index: usize,
-    pub play_start_instant: Option<std::time::Instant>,
+    fn next(&mut self) -> Option<f32> {
+        let v = self.inner.next()?;
+        self.yielded += 1;
+        self.count.store(self.yielded, Ordering::Relaxed);
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
-impl Player {
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            output: Output::new()?,
-            sink: None,
-            audio: None,
-            file_path: None,
-            playing: false,
-            speed: 1.0,
+impl<S: Source<Item = f32>> Source for CountingSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+/// The playing position, worked out from how many samples a
+/// [`CountingSource`] around a [`SliceSource`] has yielded: where the
+/// source started plus what it consumed, wrapped into the loop and stopped
+/// at the end the way the source itself plays.
+#[derive(Debug, Clone)]
+struct PlayCounter {
+    count: Arc<AtomicUsize>,
+    start: usize,        // interleaved index the source started from
+    channels: usize,     // of the audio
+    out_channels: usize, // of the source, after any mono mix
+    loop_region: Option<(usize, usize)>,
+    wraps: Option<usize>, // the loop's limit when the source started
+    total: usize,
+}
+
+impl PlayCounter {
+    /// Interleaved index of the next sample to play.
+    fn position(&self) -> usize {
+        let linear = self.linear();
+        match self.loop_region {
+            Some((a, b)) if a < b.min(self.total) => {
+                wrap_into_loop(linear, (a, b.min(self.total)), self.wraps).min(self.total)
+            }
+            _ => linear.min(self.total),
+        }
+    }
+
+    /// Start plus what was consumed, as if there were no loop.
+    fn linear(&self) -> usize {
+        let yielded = self.count.load(Ordering::Relaxed);
+        // A mono mix yields one sample (or a routed pair) per frame.
+        let consumed = if self.out_channels == self.channels {
+            yielded
+        } else {
+            yielded / self.out_channels * self.channels
+        };
+        self.start + consumed
+    }
+
+    /// Wraps the loop still has to go, `None` if unlimited (or no loop).
+    fn wraps_left(&self) -> Option<usize> {
+        let (a, b) = self.loop_region?;
+        let b = b.min(self.total);
+        let done = loop_passes(self.linear(), (a, b));
+        Some(self.wraps?.saturating_sub(done))
+    }
+
+    /// Played through to the end; never while still looping.
+    fn at_end(&self) -> bool {
+        self.position() >= self.total
+    }
+}
+
+/// Times playback from before `b` has gone back from `b` to `a` by the
+/// time it has consumed up to `linear` (start plus samples consumed).
+fn loop_passes(linear: usize, (a, b): (usize, usize)) -> usize {
+    if linear < b || a >= b {
+        0
+    } else {
+        (linear - b) / (b - a) + 1
+    }
+}
+
+/// Where `linear` lands with playback going back from `b` to `a`, at
+/// most `wraps` times and then on past `b`, or indefinitely for `None`.
+fn wrap_into_loop(linear: usize, (a, b): (usize, usize), wraps: Option<usize>) -> usize {
+    let passes = loop_passes(linear, (a, b));
+    match wraps {
+        _ if passes == 0 => linear,
+        Some(n) if passes > n => linear - n * (b - a),
+        _ => a + (linear - b) % (b - a),
+    }
+}
+
+/// Fades `inner` in from the start, and out to a hold or the end as its
+/// [`FadeControl`] says. While held it plays silence without pulling from
+/// `inner`, in whole frames so the channels stay lined up. Without a fade
+/// it passes `inner` through.
+pub struct Faded<S> {
+    inner: S,
+    fade: Option<Fade>,
+    phase: usize,  // output sample within the current frame
+    holding: bool, // the current frame is silence
+}
+
+impl<S: Source<Item = f32>> Faded<S> {
+    /// Fade over `len` output samples under `control`, if given.
+    pub fn new(inner: S, fade: Option<(usize, Arc<FadeControl>)>) -> Self {
+        Self {
+            inner,
+            fade: fade.map(|(len, control)| Fade {
+                len: len.max(1),
+                level: 0,
+                control,
+            }),
+            phase: 0,
+            holding: false,
+        }
+    }
+
+    fn frame_len(&self) -> usize {
+        self.inner.channels().max(1) as usize
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Faded<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.phase == 0 {
+            self.holding = self.fade.as_ref().is_some_and(Fade::holding);
+        }
+        let v = if self.holding {
+            0.0
+        } else {
+            let gain = match &mut self.fade {
+                Some(fade) => fade.gain()?,
+                None => 1.0,
+            };
+            self.inner.next()? * gain
+        };
+        self.phase = (self.phase + 1) % self.frame_len();
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.fade {
+            // A held source plays silence for as long as it's held.
+            Some(_) => (0, None),
+            None => self.inner.size_hint(),
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Faded<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        let inner = self.inner.current_frame_len()?;
+        if self.holding {
+            // Check back after this frame of silence.
+            Some(self.frame_len() - self.phase)
+        } else {
+            Some(inner)
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+/// Read-only playback state, see [`Player::snapshot`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackSnapshot {
+    pub loaded: bool,
+    pub playing: bool,
+    pub position_samples: usize, // interleaved
+    pub total_samples: usize,    // interleaved
+    pub position_secs: f64,
+    pub duration_secs: f64,
+    pub speed: f32,
+    pub muted: bool,
+    pub file_path: Option<std::path::PathBuf>,
+}
+
+impl PlaybackSnapshot {
+    /// Position as a 0..=1 fraction of the file.
+    pub fn fraction(&self) -> f32 {
+        if self.total_samples == 0 {
+            0.0
+        } else {
+            (self.position_samples as f32) / (self.total_samples as f32)
+        }
+    }
+
+    /// Content time left until the end of the file.
+    pub fn remaining_secs(&self) -> f64 {
+        (self.duration_secs - self.position_secs).max(0.0)
+    }
+
+    /// Real time that playing to the end takes at the current speed.
+    pub fn remaining_wall_secs(&self) -> f64 {
+        self.remaining_secs() / self.speed.max(MIN_SPEED) as f64
+    }
+}
+
+pub struct Player {
+    pub output: Output,
+    pub sink: Option<Sink>,
+
+    pub audio: Option<DecodedAudio>,
+    pub file_path: Option<std::path::PathBuf>,
+
+    pub playing: bool,
+    pub speed: f32, // MIN_SPEED..=MAX_SPEED
+    pub force_mono_output: bool,
+    pub mono_output: MonoMode,
+    pub rewind_on_pause_ms: u64, // backs up the paused position to cover reaction time
+    pub muted: bool,
+    pub volume: f32,                         // gain applied to the sink, 0.0..=2.0
+    pub norm_gain: f32,                      // the loaded file's normalization, on top of `volume`
+    pub loop_region: Option<(usize, usize)>, // interleaved [start, end), repeats while set
+    pub loop_a: Option<usize>, // A/B loop points (interleaved); both set = loop_region
+    pub loop_b: Option<usize>,
+    pub loop_repeats: u32, // times an A/B loop goes back to A before playing on; 0 = until cleared
+    loop_wraps: Option<usize>, // what is left of `loop_repeats` for the current A/B loop
+    pub sample_accurate: bool, // track position from samples consumed, not elapsed time
+    pub speed_ramp_ms: u64, // glide between speeds while playing; 0 rebuilds instantly
+    pub seek_from_committed: bool, // relative seeks count from content_index, not the live position
+    pub preserve_pitch: bool, // time-stretch instead of resampling when speed != 1
+    pub skip_silence: bool, // jump over long quiet stretches while playing
+    pub voice_filter: bool, // high-pass (+ presence boost) on playback
+    pub highpass_hz: f32,
+    pub presence_boost: bool,
+    pub fade_ms: u64, // fade in on start, out on pause/stop/rebuild; 0 cuts hard
+    fade_control: Option<Arc<FadeControl>>, // fades the current sink's source to a hold or stop
+    speed_control: Option<Arc<AtomicU32>>, // live speed (f32 bits) of the current sink
+    sink_settings: Option<SinkSettings>, // what the current sink was built with
+    paused_at: Option<usize>, // set while `sink` is paused in place at this index
+    position: Option<PlayCounter>, // samples played by the current sink's source
+
+    // playback position management
+    pub content_index: usize, // interleaved index when paused, or last seeked
+    pub play_start_index: usize,
+    pub play_start_instant: Option<std::time::Instant>,
+}
+
+impl Player {
+    pub fn new(output_device: Option<&str>) -> Result<Self> {
+        Ok(Self::with_output(Output::new(output_device)?))
+    }
+
+    /// A player on an [`Output::idle`].
+    #[cfg(test)]
+    pub fn idle() -> Self {
+        Self::with_output(Output::idle())
+    }
+
+    fn with_output(output: Output) -> Self {
+        Self {
+            output,
+            sink: None,
+            audio: None,
+            file_path: None,
+            playing: false,
+            speed: 1.0,
+            force_mono_output: false,
+            mono_output: MonoMode::Off,
+            rewind_on_pause_ms: 0,
+            muted: false,
+            volume: 1.0,
+            norm_gain: 1.0,
+            loop_region: None,
+            loop_a: None,
+            loop_b: None,
+            loop_repeats: 0,
+            loop_wraps: None,
+            sample_accurate: true,
+            position: None,
+            speed_ramp_ms: 0,
+            seek_from_committed: false,
+            preserve_pitch: true,
+            skip_silence: false,
+            voice_filter: false,
+            highpass_hz: 100.0,
+            presence_boost: false,
+            fade_ms: 0,
+            fade_control: None,
+            speed_control: None,
+            sink_settings: None,
+            paused_at: None,
             content_index: 0,
             play_start_index: 0,
             play_start_instant: None,
-        })
+        }
     }
 
-    pub fn load_file(&mut self, path: &Path) -> Result<()> {
+    /// Take over audio decoded from `path` (see [`Self::decode_options`]),
+    /// replacing whatever was loaded.
+    pub fn load_decoded(&mut self, path: &Path, decoded: DecodedAudio) {
         self.stop();
-        let decoded = decode_to_f32_interleaved(path)?;
+        self.norm_gain = decoded.norm_gain;
         self.audio = Some(decoded);
         self.file_path = Some(path.to_path_buf());
         self.content_index = 0;
+        self.loop_region = None;
+        self.loop_a = None;
+        self.loop_b = None;
+        self.play_start_index = 0;
+        self.play_start_instant = None;
+    }
+
+    /// Load audio piped on stdin. There is no source file, so archiving is
+    /// unavailable for it.
+    pub fn load_stdin(&mut self, opts: &DecodeOptions) -> Result<()> {
+        self.stop();
+        let decoded = decode_stdin_to_f32_interleaved(&self.decode_options(opts))?;
+        self.norm_gain = decoded.norm_gain;
+        self.audio = Some(decoded);
+        self.file_path = None;
+        self.content_index = 0;
+        self.loop_region = None;
+        self.loop_a = None;
+        self.loop_b = None;
         self.play_start_index = 0;
         self.play_start_instant = None;
         Ok(())
     }
 
+    /// `opts` completed with what the player knows (the device rate), for
+    /// decoding a file on another thread.
+    pub fn decode_options(&self, opts: &DecodeOptions) -> DecodeOptions {
+        DecodeOptions {
+            device_rate: self.output.sample_rate,
+            ..opts.clone()
+        }
+    }
+
     pub fn unload(&mut self) {
         self.stop();
         self.audio = None;
         self.file_path = None;
         self.content_index = 0;
+        self.loop_region = None;
+        self.loop_a = None;
+        self.loop_b = None;
         self.play_start_index = 0;
         self.play_start_instant = None;
     }
@@ -287,6 +1654,9 @@ impl Player {
         let Some(audio) = &self.audio else {
             return 0;
         };
+        if let Some(counter) = &self.position {
+            return counter.position();
+        }
         let Some(start) = self.play_start_instant else {
             return self.content_index;
         };
@@ -295,83 +1665,468 @@ impl Player {
         let delta = (elapsed * (audio.sample_rate as f64) * (self.speed as f64) * (ch as f64))
             .floor() as usize;
         let mut idx = self.play_start_index.saturating_add(delta);
+        if let Some((a, b)) = self.loop_region {
+            // Playback wraps from b to a; map the linear estimate back into it.
+            if b > a && self.play_start_index < b {
+                idx = wrap_into_loop(idx, (a, b), self.wrap_limit());
+            }
+        }
         if idx > audio.total_samples {
             idx = audio.total_samples;
         }
         idx
     }
 
-    pub fn current_time_secs(&self) -> (u64, u64) {
-        if let Some(audio) = &self.audio {
-            let ch = audio.channels as usize;
-            let idx = self.current_index_interleaved();
-            let frames = idx / ch;
-            let total_frames = audio.total_samples / ch;
-            let cur_secs = frames as f64 / audio.sample_rate as f64;
-            let total_secs = total_frames as f64 / audio.sample_rate as f64;
-            (cur_secs.floor() as u64, total_secs.floor() as u64)
-        } else {
-            (0, 0)
+    /// A consistent view of the playback state at this instant.
+    pub fn snapshot(&self) -> PlaybackSnapshot {
+        let Some(audio) = &self.audio else {
+            return PlaybackSnapshot {
+                loaded: false,
+                playing: false,
+                position_samples: 0,
+                total_samples: 0,
+                position_secs: 0.0,
+                duration_secs: 0.0,
+                speed: self.speed,
+                muted: self.muted,
+                file_path: self.file_path.clone(),
+            };
+        };
+        let ch = audio.channels as usize;
+        let sr = audio.sample_rate as f64;
+        let idx = self.current_index_interleaved();
+        PlaybackSnapshot {
+            loaded: true,
+            playing: self.playing,
+            position_samples: idx,
+            total_samples: audio.total_samples,
+            position_secs: (idx / ch) as f64 / sr,
+            duration_secs: (audio.total_samples / ch) as f64 / sr,
+            speed: self.speed,
+            muted: self.muted,
+            file_path: self.file_path.clone(),
         }
     }
 
     fn rebuild_sink_from(&mut self, start_idx: usize) {
+        if self.audio.is_none() {
+            return;
+        }
+        // Let the existing sink fade out (or drop it)
+        self.release_sink();
         if let Some(audio) = &self.audio {
-            // Drop existing sink
-            self.sink.take();
-
-            let sink = Sink::try_new(&self.output.handle).expect("Failed to create Sink");
+            let sink = self.output.new_sink();
             // Build a zero-copy source view from the current index
-            let source = SliceSource::new(
+            // With pitch preservation the source plays at its natural rate
+            // and the time-stretch consumes it `speed` times faster.
+            let stretch = self.preserve_pitch && (self.speed - 1.0).abs() > f32::EPSILON;
+            let mut source = SliceSource::new(
                 audio.samples.clone(),
                 start_idx,
                 audio.channels,
                 audio.sample_rate,
-                self.speed,
-            );
-            sink.append(source);
+                if stretch { 1.0 } else { self.speed },
+                self.force_mono_output,
+            )
+            .with_mono_output(self.mono_output);
+            let mut start_idx = start_idx;
+            let wraps = self.wrap_limit();
+            if let Some((a, b)) = self.loop_region {
+                source = source.with_loop(a, b, wraps);
+                if start_idx < a || start_idx >= b {
+                    start_idx = a;
+                }
+            }
+            if let SampleStore::Streamed(store) = &audio.samples {
+                if !store.wait_for(start_idx / store.block_len(), STREAM_START_WAIT) {
+                    warn!("Stream not decoded up to the start position yet");
+                }
+            }
+            if self.voice_filter {
+                let ch = if self.force_mono_output || self.mono_output != MonoMode::Off {
+                    1
+                } else {
+                    audio.channels
+                };
+                source = source.with_filter(VoiceFilter::new(
+                    audio.sample_rate,
+                    ch as usize,
+                    self.highpass_hz,
+                    self.presence_boost,
+                ));
+            }
+            // Ramping only works with the sample counter; elapsed-time
+            // estimation assumes a constant speed. It glides the rate, so
+            // it doesn't combine with pitch preservation.
+            self.speed_control = None;
+            if self.sample_accurate && self.speed_ramp_ms > 0 && !self.preserve_pitch {
+                let speed = Arc::new(AtomicU32::new(self.speed.to_bits()));
+                source = source.with_speed_control(
+                    audio.sample_rate,
+                    speed.clone(),
+                    Duration::from_millis(self.speed_ramp_ms),
+                );
+                self.speed_control = Some(speed);
+            }
+            let count = Arc::new(AtomicUsize::new(0));
+            self.position = self.sample_accurate.then(|| PlayCounter {
+                count: count.clone(),
+                start: start_idx,
+                channels: audio.channels as usize,
+                out_channels: source.channels() as usize,
+                loop_region: self.loop_region,
+                wraps,
+                total: audio.total_samples,
+            });
+            let source = CountingSource::new(source, count);
+            self.fade_control = None;
+            let mut fade = None;
+            if self.fade_ms > 0 {
+                let ch = source.channels() as usize;
+                let len = (self.fade_ms * audio.sample_rate as u64 / 1000) as usize * ch;
+                let control = Arc::new(FadeControl::new(FadeMode::Play));
+                fade = Some((len, control.clone()));
+                self.fade_control = Some(control);
+            }
+            let source = Faded::new(source, fade);
+            sink.set_volume(self.effective_volume());
+            if stretch {
+                sink.append(TimeStretch::new(source, self.speed));
+            } else {
+                sink.append(source);
+            }
             sink.play();
 
             self.sink = Some(sink);
+            self.sink_settings = Some(self.sink_settings());
             self.play_start_index = start_idx;
             self.play_start_instant = Some(std::time::Instant::now());
             self.playing = true;
         }
     }
 
+    /// Stop the current sink: with fades, leave it to play out its short
+    /// fade-out on its own; otherwise cut it off.
+    fn release_sink(&mut self) {
+        self.paused_at = None;
+        // Carry the wraps this source made over to the next one.
+        if let Some(left) = self.position.as_ref().and_then(PlayCounter::wraps_left) {
+            self.loop_wraps = Some(left);
+        }
+        let Some(sink) = self.sink.take() else {
+            return;
+        };
+        if sink.is_paused() {
+            // Silent already, and a detached paused sink would never end.
+            self.fade_control = None;
+            sink.stop();
+            return;
+        }
+        match self.fade_control.take() {
+            Some(control) => {
+                // From a hold this ends right away, having faded out already.
+                control.set(FadeMode::Stop);
+                sink.detach();
+            }
+            None => sink.stop(),
+        }
+    }
+
+    fn sink_settings(&self) -> SinkSettings {
+        SinkSettings {
+            speed: self.speed,
+            loop_region: self.loop_region,
+            force_mono_output: self.force_mono_output,
+            mono_output: self.mono_output,
+            preserve_pitch: self.preserve_pitch,
+            voice_filter: self.voice_filter,
+            highpass_hz: self.highpass_hz,
+            presence_boost: self.presence_boost,
+            sample_accurate: self.sample_accurate,
+            speed_ramp_ms: self.speed_ramp_ms,
+            fade_ms: self.fade_ms,
+        }
+    }
+
     pub fn play_from_current(&mut self) {
+        if self.resume_paused_sink() {
+            return;
+        }
         let idx = self.content_index;
         self.rebuild_sink_from(idx);
     }
 
+    /// Unpause the sink `pause` kept, if playback would continue exactly
+    /// where it stopped with the same settings. `false` if it must be
+    /// rebuilt instead.
+    fn resume_paused_sink(&mut self) -> bool {
+        let Some(paused_at) = self.paused_at.take() else {
+            return false;
+        };
+        let Some(sink) = &self.sink else {
+            return false;
+        };
+        if paused_at != self.content_index
+            || self.sink_settings != Some(self.sink_settings())
+            || sink.empty()
+        {
+            return false;
+        }
+        match &self.fade_control {
+            Some(control) => control.set(FadeMode::Play),
+            None => sink.play(),
+        }
+        // A fade-out runs on a little past where the pause was asked for;
+        // the counter knows where the source is really holding.
+        if let Some(counter) = &self.position {
+            self.content_index = counter.position();
+        }
+        self.playing = true;
+        self.play_start_index = self.content_index;
+        self.play_start_instant = Some(std::time::Instant::now());
+        true
+    }
+
     pub fn pause(&mut self) {
         if self.playing {
-            // Update content_index to current
+            // Update content_index to current, backed up by rewind_on_pause_ms
             let idx = self.current_index_interleaved();
-            self.content_index = idx;
-            if let Some(sink) = self.sink.take() {
-                sink.stop();
-            }
+            self.content_index = self.rewound_for_pause(idx);
             self.playing = false;
             self.play_start_instant = None;
+            // Keep the source where it is so a plain resume is instant:
+            // with fades it fades out and holds, then fades back in on
+            // resume. Not when backing up, which needs a new source anyway.
+            if self.content_index == idx {
+                if let Some(sink) = &self.sink {
+                    match &self.fade_control {
+                        Some(control) => control.set(FadeMode::Hold),
+                        None => sink.pause(),
+                    }
+                    self.paused_at = Some(idx);
+                    return;
+                }
+            }
+            self.release_sink();
+            self.position = None;
+            self.speed_control = None;
         }
     }
 
-    pub fn stop(&mut self) {
-        if let Some(sink) = self.sink.take() {
-            sink.stop();
+    /// `idx` backed up by `rewind_on_pause_ms`.
+    fn rewound_for_pause(&self, idx: usize) -> usize {
+        let Some(audio) = &self.audio else {
+            return idx;
+        };
+        let back_frames = self.rewind_on_pause_ms * audio.sample_rate as u64 / 1000;
+        idx.saturating_sub(back_frames as usize * audio.channels as usize)
+    }
+
+    /// Reopen output on `device` (see [`Output::new`]), carrying on from
+    /// the current position.
+    pub fn set_output_device(&mut self, device: Option<&str>) -> Result<()> {
+        let output = Output::new(device)?;
+        let was_playing = self.playing;
+        self.pause();
+        // A sink kept by `pause` belongs to the old device.
+        self.release_sink();
+        self.output = output;
+        if was_playing {
+            self.play_from_current();
         }
+        Ok(())
+    }
+
+    /// Go back to `idx` (a position recorded earlier), backed up by
+    /// `rewind_on_pause_ms`, and resume playback if `play`.
+    pub fn resume_from(&mut self, idx: usize, play: bool) {
+        let Some(total) = self.audio.as_ref().map(|a| a.total_samples) else {
+            return;
+        };
+        self.pause();
+        self.content_index = self.rewound_for_pause(idx.min(total));
+        if play {
+            self.play_from_current();
+        }
+    }
+
+    /// Commit the live position into `content_index` without pausing, so
+    /// later seeks are relative to a known point.
+    pub fn commit_position(&mut self) {
+        if !self.playing {
+            return;
+        }
+        let Some(total) = self.audio.as_ref().map(|a| a.total_samples) else {
+            return;
+        };
+        // Without a counter, once the sink has drained the estimate is
+        // meaningless; pin to end.
+        let drained = match &self.position {
+            Some(counter) => counter.at_end(),
+            None => self.sink.as_ref().map(|s| s.empty()).unwrap_or(true),
+        };
+        let idx = if drained {
+            total
+        } else {
+            self.current_index_interleaved()
+        };
+        self.content_index = idx;
+        self.play_start_index = idx;
+        self.play_start_instant = Some(std::time::Instant::now());
+    }
+
+    /// Loop the last `secs` before the current position and play it. While
+    /// playing the region ends at the live position.
+    pub fn loop_last(&mut self, secs: u32) {
+        let Some(audio) = &self.audio else {
+            return;
+        };
+        let ch = audio.channels as usize;
+        let back = secs as usize * audio.sample_rate as usize * ch;
+        let end = self.current_index_interleaved() / ch * ch;
+        let start = end.saturating_sub(back);
+        if start >= end {
+            return;
+        }
+        self.loop_region = Some((start, end));
+        self.loop_a = None;
+        self.loop_b = None;
+        self.content_index = start;
+        self.rebuild_sink_from(start);
+    }
+
+    /// Mark the current position as loop point A. Once A and B are both
+    /// set, the passage between them repeats.
+    pub fn set_loop_a(&mut self) {
+        self.loop_a = self.frame_aligned_position();
+        self.apply_ab_loop();
+    }
+
+    /// Mark the current position as loop point B, see [`Self::set_loop_a`].
+    pub fn set_loop_b(&mut self) {
+        self.loop_b = self.frame_aligned_position();
+        self.apply_ab_loop();
+    }
+
+    fn frame_aligned_position(&self) -> Option<usize> {
+        let ch = self.audio.as_ref()?.channels as usize;
+        Some(self.current_index_interleaved() / ch * ch)
+    }
+
+    /// Turn A/B into the loop region, swapping them if A comes after B.
+    fn apply_ab_loop(&mut self) {
+        let (Some(a), Some(b)) = (self.loop_a, self.loop_b) else {
+            return;
+        };
+        let (a, b) = (a.min(b), a.max(b));
+        self.loop_a = Some(a);
+        self.loop_b = Some(b);
+        if a == b {
+            return;
+        }
+        self.loop_wraps = (self.loop_repeats > 0).then_some(self.loop_repeats as usize);
+        let idx = self.current_index_interleaved();
+        self.loop_region = Some((a, b));
+        if self.playing {
+            // Jumps to A when the position is outside the new region.
+            self.rebuild_sink_from(idx);
+        }
+    }
+
+    fn is_ab_loop(&self) -> bool {
+        self.loop_region.is_some() && self.loop_a.is_some() && self.loop_b.is_some()
+    }
+
+    /// Wraps left for the loop region; only an A/B loop is counted.
+    fn wrap_limit(&self) -> Option<usize> {
+        self.loop_wraps.filter(|_| self.is_ab_loop())
+    }
+
+    /// Repeat an A/B loop `repeats` times (0: until cleared), counting
+    /// afresh if one is running.
+    pub fn set_loop_repeats(&mut self, repeats: u32) {
+        self.loop_repeats = repeats;
+        if self.is_ab_loop() {
+            self.loop_wraps = (repeats > 0).then_some(repeats as usize);
+            if self.playing {
+                let idx = self.current_index_interleaved();
+                self.content_index = idx;
+                self.rebuild_sink_from(idx);
+            }
+        }
+    }
+
+    /// Once a counted A/B loop has made its last wrap and playback is past
+    /// B, drop the loop; the source already plays on by itself.
+    pub fn finish_counted_loop(&mut self) {
+        let (Some((_, b)), Some(_)) = (self.loop_region, self.wrap_limit()) else {
+            return;
+        };
+        if !self.playing {
+            return;
+        }
+        let idx = self.current_index_interleaved();
+        if idx < b {
+            return;
+        }
+        info!("A/B loop done after {} repeats", self.loop_repeats);
+        self.loop_region = None;
+        self.loop_a = None;
+        self.loop_b = None;
+        self.loop_wraps = None;
+        // The estimate starts over from here, now without the loop.
+        self.play_start_index = idx;
+        self.play_start_instant = Some(Instant::now());
+        if let Some(settings) = &mut self.sink_settings {
+            settings.loop_region = None;
+        }
+    }
+
+    /// Relative seeks and progress-bar jumps leave a "loop last" region but
+    /// stay in an A/B loop, which only Clear Loop, Home/End or running out
+    /// of repeats ends.
+    fn leave_loop_last(&mut self) {
+        if !self.is_ab_loop() {
+            self.loop_region = None;
+        }
+    }
+
+    /// Drop the loop region and A/B points; playback (if any) continues
+    /// from where it is.
+    pub fn clear_loop(&mut self) {
+        self.loop_a = None;
+        self.loop_b = None;
+        if self.loop_region.is_none() {
+            return;
+        }
+        let idx = self.current_index_interleaved();
+        self.loop_region = None;
+        self.content_index = idx;
+        if self.playing {
+            self.rebuild_sink_from(idx);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.release_sink();
         self.playing = false;
         self.play_start_instant = None;
+        self.position = None;
+        self.speed_control = None;
     }
 
     pub fn seek_seconds(&mut self, delta_seconds: i64) {
+        self.leave_loop_last();
         if let Some(audio) = &self.audio {
             let ch = audio.channels as usize;
             let total = audio.total_samples;
             let delta_samples = ((delta_seconds as f64) * (audio.sample_rate as f64) * (ch as f64))
                 .floor() as isize;
-            let base_idx = self.current_index_interleaved() as isize;
+            let base_idx = if self.seek_from_committed {
+                self.content_index
+            } else {
+                self.current_index_interleaved()
+            } as isize;
             let mut new_idx = base_idx + delta_samples;
             if new_idx < 0 {
                 new_idx = 0;
@@ -387,24 +2142,675 @@ impl Player {
         }
     }
 
-    pub fn set_speed(&mut self, speed: f32) {
-        self.speed = speed.max(0.1);
+    /// Jump to interleaved `idx` (snapped to a frame), keeping the current
+    /// play/pause state. At or past the end playback stops there, which
+    /// `clamp_at_end_if_needed` then leaves alone.
+    pub fn seek_to_index(&mut self, idx: usize) {
+        self.leave_loop_last();
+        let Some(audio) = &self.audio else {
+            return;
+        };
+        let ch = audio.channels as usize;
+        let total = audio.total_samples;
+        if idx >= total {
+            self.pause();
+            self.content_index = total;
+            return;
+        }
+        self.content_index = idx / ch * ch;
+        if self.playing {
+            self.rebuild_sink_from(self.content_index);
+        }
+    }
+
+    /// Home/End: drop any loop and jump to the start (or the end) of the
+    /// buffer, keeping the play/pause state as [`Self::seek_to_index`] does.
+    pub fn jump_to_edge(&mut self, to_end: bool) {
+        let Some(total) = self.audio.as_ref().map(|a| a.total_samples) else {
+            return;
+        };
+        self.loop_region = None;
+        self.loop_a = None;
+        self.loop_b = None;
+        self.seek_to_index(if to_end { total } else { 0 });
+    }
+
+    /// Pause and move to the start of the buffer.
+    pub fn seek_to_start(&mut self) {
+        self.loop_region = None;
+        self.loop_a = None;
+        self.loop_b = None;
+        if self.audio.is_some() {
+            self.pause();
+            self.content_index = 0;
+        }
+    }
+
+    /// With `skip_silence`, when playback is in a stretch whose RMS stays
+    /// below `threshold_db` (dBFS) for at least `min_ms` more, jump to just
+    /// before it ends. Not while looping. Returns `true` if it skipped.
+    pub fn skip_silence_if_needed(&mut self, threshold_db: f32, min_ms: u64) -> bool {
+        if !self.skip_silence || !self.playing || self.loop_region.is_some() {
+            return false;
+        }
+        let Some(audio) = &self.audio else {
+            return false;
+        };
+        let ch = audio.channels as usize;
+        let sr = audio.sample_rate as usize;
+        let window = (sr * SILENCE_WINDOW_MS / 1000).max(1) * ch;
+        let min_len = (sr as u64 * min_ms / 1000) as usize * ch;
+        let limit = 10f32.powf(threshold_db / 20.0);
+        let limit_sq = limit * limit * window as f32;
+
+        let start = self.current_index_interleaved() / ch * ch;
+        let mut end = start;
+        // Only decoded audio counts: a streamed block that isn't ready
+        // ends the stretch rather than passing for silence.
+        let mut reader = SampleReader::new(audio.samples.clone());
+        while end + window <= audio.total_samples {
+            let energy: Option<f32> = (end..end + window)
+                .map(|i| reader.try_get(i).map(|v| v * v))
+                .sum();
+            if energy.is_none_or(|e| e > limit_sq) {
+                break;
+            }
+            end += window;
+        }
+        if end - start < min_len {
+            return false;
+        }
+        let lead = sr * SILENCE_LEAD_MS / 1000 * ch;
+        let target = end.saturating_sub(lead).max(start);
+        debug!(
+            "Skipping {:.2}s of silence",
+            (target - start) as f64 / (sr * ch) as f64
+        );
+        self.seek_to_index(target);
+        true
+    }
+
+    /// Switch the voice filter live; playback continues where it is.
+    pub fn set_voice_filter(&mut self, on: bool) {
+        self.voice_filter = on;
         if self.playing {
-            // Continue from current content position under new speed.
             let idx = self.current_index_interleaved();
             self.content_index = idx;
-            self.rebuild_sink_from(self.content_index);
+            self.rebuild_sink_from(idx);
+        }
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        if let Some(sink) = &self.sink {
+            sink.set_volume(self.effective_volume());
         }
     }
 
-    pub fn clamp_at_end_if_needed(&mut self) {
+    pub fn toggle_mute(&mut self) {
+        self.set_muted(!self.muted);
+    }
+
+    pub fn set_volume(&mut self, gain: f32) {
+        self.volume = gain.clamp(0.0, 2.0);
+        if let Some(sink) = &self.sink {
+            sink.set_volume(self.effective_volume());
+        }
+    }
+
+    fn effective_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume * self.norm_gain
+        }
+    }
+
+    /// Short tone mixed straight into the output, independent of the main sink.
+    pub fn play_cue(&self, freq: f32) {
+        self.play_tone(freq, Duration::from_millis(40));
+    }
+
+    /// Like [`Self::play_cue`] but with a chosen length.
+    pub fn play_tone(&self, freq: f32, len: Duration) {
+        let tone = SineWave::new(freq).take_duration(len).amplify(0.15);
+        let Some((_, handle)) = &self.output.stream else {
+            return;
+        };
+        if let Err(e) = handle.play_raw(tone) {
+            warn!("Cue playback failed: {}", e);
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        if self.playing {
+            if let Some(control) = &self.speed_control {
+                // The source glides to the new rate by itself.
+                control.store(self.speed.to_bits(), Ordering::Relaxed);
+                return;
+            }
+            // Continue under the new speed from the exact consumed-sample
+            // count when it is tracked, snapped to a frame boundary so the
+            // new source doesn't start mid-frame with its channels swapped.
+            let ch = self
+                .audio
+                .as_ref()
+                .map(|a| a.channels as usize)
+                .unwrap_or(1);
+            let idx = self.current_index_interleaved() / ch * ch;
+            self.content_index = idx;
+            self.rebuild_sink_from(idx);
+        }
+    }
+
+    /// Stop at the end of the buffer. Returns `true` only on the call that
+    /// stopped running playback, so callers can react once.
+    pub fn clamp_at_end_if_needed(&mut self) -> bool {
         if let Some(total) = self.audio.as_ref().map(|a| a.total_samples) {
-            let idx = self.current_index_interleaved();
-            if idx >= total {
+            let ended = match &self.position {
+                Some(counter) if self.playing => counter.at_end(),
+                _ => self.current_index_interleaved() >= total,
+            };
+            if ended {
+                let was_playing = self.playing;
                 // Stop playback at end
                 self.pause();
                 self.content_index = total;
+                return was_playing;
             }
         }
+        false
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::stream::PREFETCH_AHEAD;
+
+    fn store(samples: Vec<f32>) -> SampleStore {
+        SampleStore::F32(Arc::new(samples))
+    }
+
+    #[test]
+    fn peak_normalization_lifts_the_peak_to_the_target() {
+        let gain = normalization_gain(&store(vec![0.1, -0.5, 0.25]), NormalizeMode::Peak);
+        assert!((gain - NORM_PEAK_TARGET / 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn normalization_gain_is_capped() {
+        let quiet = store(vec![0.001, -0.001]);
+        assert_eq!(
+            normalization_gain(&quiet, NormalizeMode::Peak),
+            NORM_MAX_GAIN
+        );
+        assert_eq!(
+            normalization_gain(&store(vec![0.0; 4]), NormalizeMode::Rms),
+            1.0
+        );
+        assert_eq!(normalization_gain(&quiet, NormalizeMode::Off), 1.0);
+    }
+
+    #[test]
+    fn rms_normalization_never_pushes_the_peak_over() {
+        // A lone spike: the RMS target would need far more gain than the
+        // peak allows.
+        let mut samples = vec![0.01; 1000];
+        samples[500] = 0.8;
+        let gain = normalization_gain(&store(samples), NormalizeMode::Rms);
+        assert!((gain - NORM_PEAK_TARGET / 0.8).abs() < 1e-6);
+    }
+
+    /// `secs` of stereo at 1 kHz, each sample its own interleaved index.
+    /// Also the app tests' fixture.
+    pub fn decoded(secs: usize) -> DecodedAudio {
+        let total = secs * 1000 * 2;
+        DecodedAudio {
+            samples: indexed(total),
+            sample_rate: 1000,
+            channels: 2,
+            total_samples: total,
+            codec: "pcm",
+            skipped_packets: 0,
+            source: SourceFormat {
+                sample_rate: 1000,
+                channels: 2,
+                bits_per_sample: Some(16),
+                bitrate_kbps: None,
+            },
+            norm_gain: 1.0,
+        }
+    }
+
+    #[test]
+    fn snapshot_follows_a_sequence_of_operations() {
+        let mut player = Player::idle();
+        let snap = player.snapshot();
+        assert!(!snap.loaded && !snap.playing);
+        assert_eq!(snap.file_path, None);
+
+        player.preserve_pitch = false;
+        player.load_decoded(Path::new("/in/memo.wav"), decoded(10));
+        let snap = player.snapshot();
+        assert!(snap.loaded && !snap.playing);
+        assert_eq!(snap.file_path.as_deref(), Some(Path::new("/in/memo.wav")));
+        assert_eq!((snap.position_samples, snap.total_samples), (0, 20_000));
+        assert_eq!(snap.duration_secs, 10.0);
+
+        player.seek_to_index(4_001); // snapped to the frame
+        player.play_from_current();
+        player.output.pull(1_000);
+        let snap = player.snapshot();
+        assert!(snap.playing);
+        assert_eq!(snap.position_samples, 5_000);
+        assert_eq!(snap.position_secs, 2.5);
+
+        player.set_speed(1.5);
+        player.set_muted(true);
+        player.output.pull(600);
+        player.pause();
+        let snap = player.snapshot();
+        assert!(!snap.playing && snap.muted);
+        assert_eq!(snap.speed, 1.5);
+        assert_eq!(snap.position_samples, 5_600);
+        assert_eq!(snap.remaining_secs(), 7.2);
+
+        player.seek_seconds(-3);
+        assert_eq!(player.snapshot().position_samples, 0);
+        player.unload();
+        assert!(!player.snapshot().loaded);
+    }
+
+    /// A player on an idle output with `secs` of [`decoded`] audio loaded.
+    fn player(secs: usize) -> Player {
+        let mut player = Player::idle();
+        player.preserve_pitch = false;
+        player.load_decoded(Path::new("/in/memo.wav"), decoded(secs));
+        player
+    }
+
+    #[test]
+    fn a_speed_change_carries_on_from_the_last_sample_played() {
+        for ramp_ms in [0, 200] {
+            let mut player = player(10);
+            player.speed_ramp_ms = ramp_ms;
+            player.play_from_current();
+            for speed in [1.5, 0.75, 2.0] {
+                let last = *player.output.pull(1_234).last().unwrap() as usize;
+                let before = player.snapshot().position_samples;
+                assert_eq!(before, last + 1);
+                player.set_speed(speed);
+                let after = player.snapshot().position_samples;
+                assert!(after <= before && before - after < 2, "{before} -> {after}");
+                // The next sample heard follows on within a frame.
+                let next = player.output.pull(1)[0] as usize;
+                assert!(next >= last && next - last <= 2, "{last} -> {next}");
+            }
+        }
+    }
+
+    #[test]
+    fn seeks_count_from_the_live_or_the_committed_position() {
+        // Seek to 2 s, play 3 s, then back 1 s.
+        let seek_back = |committed: bool, commit: bool| {
+            let mut player = player(10);
+            player.seek_from_committed = committed;
+            player.seek_to_index(4_000);
+            player.play_from_current();
+            player.output.pull(6_000);
+            if commit {
+                player.commit_position();
+            }
+            player.seek_seconds(-1);
+            player.output.pull(1)[0] as usize
+        };
+        assert_eq!(seek_back(false, false), 8_000);
+        assert_eq!(seek_back(true, false), 2_000);
+        assert_eq!(seek_back(true, true), 8_000);
+    }
+
+    /// Samples whose values are their own interleaved index.
+    fn indexed(len: usize) -> SampleStore {
+        store((0..len).map(|i| i as f32).collect())
+    }
+
+    fn counted(
+        src: SliceSource,
+        start: usize,
+        loop_region: Option<(usize, usize)>,
+    ) -> (CountingSource<SliceSource>, PlayCounter) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = PlayCounter {
+            count: count.clone(),
+            start,
+            channels: src.channels as usize,
+            out_channels: src.channels() as usize,
+            loop_region,
+            wraps: src.wraps_left,
+            total: src.data.len(),
+        };
+        (CountingSource::new(src, count), counter)
+    }
+
+    #[test]
+    fn position_is_the_next_sample_to_be_consumed() {
+        let src = SliceSource::new(indexed(100), 10, 2, 8000, 1.0, false);
+        let (mut src, counter) = counted(src, 10, None);
+        assert_eq!(counter.position(), 10);
+        for _ in 0..37 {
+            let consumed = src.next().unwrap();
+            assert_eq!(counter.position(), consumed as usize + 1);
+        }
+    }
+
+    #[test]
+    fn position_of_a_mono_mix_steps_by_frames() {
+        let src = SliceSource::new(indexed(100), 10, 2, 8000, 1.0, true);
+        let (mut src, counter) = counted(src, 10, None);
+        // Each mixed sample is the mean of a frame, (2i + 2i + 1) / 2.
+        for _ in 0..5 {
+            let mixed = src.next().unwrap();
+            assert_eq!(counter.position(), (mixed - 0.5) as usize + 2);
+        }
+        assert_eq!(counter.position(), 20);
+    }
+
+    #[test]
+    fn position_wraps_with_the_loop() {
+        let src = SliceSource::new(indexed(100), 50, 2, 8000, 1.0, false).with_loop(20, 40, None);
+        // Started outside the loop: the source jumps to its start.
+        assert_eq!(src.pos, 20);
+        let (mut src, counter) = counted(src, 20, Some((20, 40)));
+        for _ in 0..95 {
+            let consumed = src.next().unwrap() as usize;
+            let next = if consumed + 1 == 40 { 20 } else { consumed + 1 };
+            assert_eq!(counter.position(), next);
+            assert!(!counter.at_end());
+        }
+    }
+
+    #[test]
+    fn a_counted_loop_wraps_that_often_then_plays_on() {
+        let src =
+            SliceSource::new(indexed(100), 20, 2, 8000, 1.0, false).with_loop(20, 40, Some(2));
+        let (src, counter) = counted(src, 20, Some((20, 40)));
+        let played: Vec<usize> = src.map(|v| v as usize).collect();
+        let expected: Vec<usize> = (20..40).chain(20..40).chain(20..100).collect();
+        assert_eq!(played, expected);
+        assert_eq!(counter.position(), 100);
+        assert_eq!(counter.wraps_left(), Some(0));
+        assert!(counter.at_end());
+    }
+
+    #[test]
+    fn an_ab_loop_repeats_across_a_rebuild_then_lets_go() {
+        let mut player = player(10);
+        player.set_loop_repeats(2);
+        player.seek_to_index(1_000);
+        player.set_loop_a();
+        player.seek_to_index(1_400);
+        player.set_loop_b();
+        player.play_from_current();
+        let mut heard: Vec<usize> = player
+            .output
+            .pull(600)
+            .iter()
+            .map(|&v| v as usize)
+            .collect();
+        // Mid second pass: a new source carries on with one wrap left.
+        player.set_speed(1.5);
+        heard.extend(player.output.pull(900).iter().map(|&v| v as usize));
+        let expected: Vec<usize> = (1_000..1_400)
+            .chain(1_000..1_400)
+            .chain(1_000..1_700)
+            .collect();
+        assert_eq!(heard, expected);
+
+        player.finish_counted_loop();
+        assert_eq!(player.loop_region, None);
+        assert_eq!(player.snapshot().position_samples, 1_700);
+        assert_eq!(player.output.pull(1)[0] as usize, 1_700);
+    }
+
+    #[test]
+    fn position_stops_at_the_end() {
+        let src = SliceSource::new(indexed(100), 90, 2, 8000, 1.0, false);
+        let (src, counter) = counted(src, 90, None);
+        assert!(!counter.at_end());
+        assert_eq!(src.count(), 10);
+        assert_eq!(counter.position(), 100);
+        assert!(counter.at_end());
+    }
+
+    #[test]
+    fn a_held_source_fades_out_holds_in_place_and_fades_back_in() {
+        // Stereo, every sample 1.0; fades over 4 output samples.
+        let control = Arc::new(FadeControl::new(FadeMode::Play));
+        let src = SliceSource::new(store(vec![1.0; 200]), 0, 2, 8000, 1.0, false);
+        let (src, counter) = counted(src, 0, None);
+        let mut src = Faded::new(src, Some((4, control.clone())));
+        let gains: Vec<f32> = src.by_ref().take(6).collect();
+        assert_eq!(gains, [0.25, 0.5, 0.75, 1.0, 1.0, 1.0]);
+
+        // Ask mid-frame: the fade-out runs, then silence starts on a frame
+        // boundary and the source stops advancing.
+        src.next();
+        control.set(FadeMode::Hold);
+        let out: Vec<f32> = src.by_ref().take(5).collect();
+        assert_eq!(out, [0.75, 0.5, 0.25, 0.0, 0.0]);
+        let held = counter.position();
+        assert_eq!(held % 2, 0);
+        assert!(src.by_ref().take(100).all(|v| v == 0.0));
+        assert_eq!(counter.position(), held);
+
+        control.set(FadeMode::Play);
+        let out: Vec<f32> = src.by_ref().take(5).collect();
+        assert_eq!(out, [0.25, 0.5, 0.75, 1.0, 1.0]);
+        assert_eq!(counter.position(), held + 5);
+    }
+
+    #[test]
+    fn a_stopped_source_ends_after_its_fade_out() {
+        let control = Arc::new(FadeControl::new(FadeMode::Play));
+        let src = SliceSource::new(store(vec![1.0; 200]), 0, 1, 8000, 1.0, false);
+        let mut src = Faded::new(src, Some((4, control.clone())));
+        src.by_ref().take(10).count();
+        control.set(FadeMode::Hold);
+        src.by_ref().take(10).count();
+        // Already faded out: ends straight away.
+        control.set(FadeMode::Stop);
+        assert_eq!(src.next(), None);
+
+        let control = Arc::new(FadeControl::new(FadeMode::Play));
+        let src = SliceSource::new(store(vec![1.0; 200]), 0, 1, 8000, 1.0, false);
+        let src = Faded::new(src, Some((4, control.clone())));
+        control.set(FadeMode::Stop);
+        assert_eq!(src.count(), 0);
+    }
+
+    /// Write interleaved 16-bit `samples` as a plain PCM WAV file.
+    fn write_wav(path: &Path, channels: u16, rate: u32, samples: &[i16]) {
+        let data_len = samples.len() as u32 * 2;
+        let mut b = Vec::new();
+        b.extend_from_slice(b"RIFF");
+        b.extend_from_slice(&(36 + data_len).to_le_bytes());
+        b.extend_from_slice(b"WAVEfmt ");
+        b.extend_from_slice(&16u32.to_le_bytes());
+        b.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        b.extend_from_slice(&channels.to_le_bytes());
+        b.extend_from_slice(&rate.to_le_bytes());
+        b.extend_from_slice(&(rate * channels as u32 * 2).to_le_bytes());
+        b.extend_from_slice(&(channels * 2).to_le_bytes());
+        b.extend_from_slice(&16u16.to_le_bytes());
+        b.extend_from_slice(b"data");
+        b.extend_from_slice(&data_len.to_le_bytes());
+        for v in samples {
+            b.extend_from_slice(&v.to_le_bytes());
+        }
+        std::fs::write(path, b).unwrap();
+    }
+
+    #[test]
+    fn six_channels_are_folded_to_stereo_with_downmix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("surround.wav");
+        // Front left only, in FL FR FC LFE RL RR order.
+        let frame = [16_384, 0, 0, 0, 0, 0];
+        write_wav(&path, 6, 8000, &frame.repeat(100));
+
+        let err = decode_to_f32_interleaved(&path, &DecodeOptions::default()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<UnsupportedChannels>(),
+            Some(UnsupportedChannels(6))
+        ));
+
+        let opts = DecodeOptions {
+            downmix: true,
+            ..Default::default()
+        };
+        let audio = decode_to_f32_interleaved(&path, &opts).unwrap();
+        assert_eq!((audio.channels, audio.total_samples), (2, 200));
+        assert_eq!(audio.source.channels, 6);
+        let (l, r) = (audio.samples.get(100), audio.samples.get(101));
+        assert!(l > 0.25 && r.abs() < 1e-6, "{l} {r}");
+    }
+
+    #[test]
+    fn a_wav_named_mp3_still_decodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memo.mp3");
+        write_wav(&path, 1, 8000, &[0, 8_192, -8_192, 16_384]);
+        let audio = decode_to_f32_interleaved(&path, &DecodeOptions::default()).unwrap();
+        assert_eq!((audio.channels, audio.sample_rate), (1, 8000));
+        let samples: Vec<f32> = (0..audio.total_samples)
+            .map(|i| audio.samples.get(i))
+            .collect();
+        assert_eq!(samples, [0.0, 0.25, -0.25, 0.5]);
+    }
+
+    #[test]
+    fn i16_storage_holds_16_bit_sources_exactly() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memo.wav");
+        let pcm: Vec<i16> = (0..4000)
+            .map(|i| (i * 37 % 65_536 - 32_768) as i16)
+            .collect();
+        write_wav(&path, 2, 8000, &pcm);
+        let wide = decode_to_f32_interleaved(&path, &DecodeOptions::default()).unwrap();
+        let opts = DecodeOptions {
+            store: SampleStorage::I16,
+            ..DecodeOptions::default()
+        };
+        let narrow = decode_to_f32_interleaved(&path, &opts).unwrap();
+        let SampleStore::I16(stored) = &narrow.samples else {
+            panic!("not stored as i16");
+        };
+        assert_eq!(stored.as_slice(), pcm.as_slice());
+        assert_eq!(narrow.total_samples, wide.total_samples);
+        for i in 0..pcm.len() {
+            assert_eq!(narrow.samples.get(i), wide.samples.get(i));
+        }
+    }
+
+    #[test]
+    fn resample_on_load_plays_at_the_device_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memo.wav");
+        // One second of a 100 Hz tone at 8 kHz stereo.
+        let pcm: Vec<i16> = (0..16_000)
+            .map(|i| ((i / 2) as f32 * std::f32::consts::TAU / 80.0).sin() * 16_384.0)
+            .map(|v| v as i16)
+            .collect();
+        write_wav(&path, 2, 8000, &pcm);
+        let opts = DecodeOptions {
+            resample_on_load: true,
+            device_rate: Some(16_000),
+            ..DecodeOptions::default()
+        };
+        let audio = decode_to_f32_interleaved(&path, &opts).unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (16_000, 2));
+        assert_eq!(audio.total_samples, 32_000);
+        assert_eq!(audio.source.sample_rate, 8000);
+        // Past the resampler's edges the tone keeps its level.
+        let peak = (4000..28_000)
+            .map(|i| audio.samples.get(i).abs())
+            .fold(0.0, f32::max);
+        assert!((peak - 0.5).abs() < 0.02, "peak {peak}");
+
+        // A file already at the device rate is left alone.
+        let same = DecodeOptions {
+            device_rate: Some(8000),
+            ..opts
+        };
+        let audio = decode_to_f32_interleaved(&path, &same).unwrap();
+        assert_eq!((audio.sample_rate, audio.total_samples), (8000, 16_000));
+    }
+
+    #[test]
+    fn a_streamed_file_is_decoded_ahead_and_dropped_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("long.wav");
+        // Twelve one-second blocks at 1 kHz mono.
+        let pcm: Vec<i16> = (0..12_000).map(|i| (i % 1000) as i16 * 8).collect();
+        write_wav(&path, 1, 1000, &pcm);
+        let opts = DecodeOptions {
+            stream_above_bytes: Some(0),
+            ..DecodeOptions::default()
+        };
+        let audio = decode_to_f32_interleaved(&path, &opts).unwrap();
+        let SampleStore::Streamed(store) = &audio.samples else {
+            panic!("not streamed");
+        };
+        assert_eq!((store.len(), store.block_len()), (12_000, 1000));
+
+        let wait = Duration::from_secs(5);
+        assert!(store.wait_for(0, wait));
+        // The worker carries on ahead of the playhead unasked.
+        let started = Instant::now();
+        while store.cached(PREFETCH_AHEAD - 1).is_none() && started.elapsed() < wait {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(store.cached(PREFETCH_AHEAD - 1).is_some());
+
+        // Far enough on, the start falls out of the cache and reads as not
+        // ready instead of being decoded by the reader.
+        assert!(store.wait_for(11, wait));
+        let mut reader = SampleReader::new(audio.samples.clone());
+        assert_eq!(reader.try_get(0), None);
+        for i in [11_000, 11_001, 11_999] {
+            assert_eq!(reader.try_get(i), Some(f32::from_sample(pcm[i])));
+        }
+    }
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn flac_fixture_decodes_to_its_samples() {
+        let audio =
+            decode_to_f32_interleaved(&fixture("ramp.flac"), &DecodeOptions::default()).unwrap();
+        assert_eq!(audio.codec, "flac");
+        assert_eq!((audio.channels, audio.sample_rate), (2, 8000));
+        assert_eq!(audio.total_samples, 1600);
+        for i in 0..800 {
+            let left = ((i * 37 % 2000) as f32 - 1000.0) * 16.0 / 32768.0;
+            assert_eq!(audio.samples.get(2 * i), left);
+            assert_eq!(audio.samples.get(2 * i + 1), -left);
+        }
+    }
+
+    #[test]
+    fn m4a_fixture_decodes() {
+        let audio =
+            decode_to_f32_interleaved(&fixture("silence.m4a"), &DecodeOptions::default()).unwrap();
+        assert_eq!(audio.codec, "aac");
+        assert_eq!((audio.channels, audio.sample_rate), (1, 8000));
+        assert_eq!(audio.total_samples, 8 * 1024);
+        assert!((0..audio.total_samples).all(|i| audio.samples.get(i) == 0.0));
     }
 }
@@ -0,0 +1,52 @@
+use log::warn;
+use std::path::PathBuf;
+
+/// Command-line options.
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    /// Keep config and sidecars next to the executable.
+    pub portable: bool,
+    /// Decode audio piped on stdin (`cmd | transcribeupl --stdin-wav`).
+    /// The whole stream is buffered before playback; archiving is disabled.
+    pub stdin: bool,
+    /// Print what decoding `<file>` yields and exit (`--probe <file>`).
+    pub probe: Option<PathBuf>,
+    /// Machine-readable output for `--probe`.
+    pub json: bool,
+    /// Audio file to open on start (positional argument).
+    pub file: Option<PathBuf>,
+    /// Config file to use instead of the default (`--config <path>`).
+    pub config: Option<PathBuf>,
+    /// Pedal device to use regardless of the config (`--device <path>`).
+    pub device: Option<PathBuf>,
+}
+
+impl CliArgs {
+    pub fn parse() -> Self {
+        let mut args = Self::default();
+        let mut it = std::env::args_os().skip(1);
+        while let Some(a) = it.next() {
+            match a.to_str() {
+                Some("--portable") => args.portable = true,
+                Some("--stdin-wav") => args.stdin = true,
+                Some("--probe") => match it.next() {
+                    Some(path) => args.probe = Some(PathBuf::from(path)),
+                    None => warn!("--probe needs a file argument"),
+                },
+                Some("--json") => args.json = true,
+                Some("--config") => match it.next() {
+                    Some(path) => args.config = Some(PathBuf::from(path)),
+                    None => warn!("--config needs a file argument"),
+                },
+                Some("--device") => match it.next() {
+                    Some(path) => args.device = Some(PathBuf::from(path)),
+                    None => warn!("--device needs a device path argument"),
+                },
+                Some(s) if s.starts_with("--") => warn!("Ignoring unknown argument: {}", s),
+                _ if args.file.is_none() => args.file = Some(PathBuf::from(a)),
+                _ => warn!("Ignoring extra argument: {}", a.to_string_lossy()),
+            }
+        }
+        args
+    }
+}
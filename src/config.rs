@@ -1,34 +1,611 @@
-use directories::ProjectDirs;
+use crate::fsutil::write_atomic;
+use directories::{ProjectDirs, UserDirs};
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::OnceLock;
 use std::{fs, path::PathBuf};
 
+const PORTABLE_MARKER: &str = "portable.txt";
+
+// Exe dir when running portable; decided once per process.
+static PORTABLE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+// Config file given with `--config`, taking precedence over the above.
+static CONFIG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
 pub const DEFAULT_VENDOR_ID: u16 = 0x0911;
 pub const DEFAULT_PRODUCT_ID: u16 = 0x1844;
 pub const DEFAULT_LEFT_CODE: u32 = 288;
 pub const DEFAULT_MIDDLE_CODE: u32 = 290;
 pub const DEFAULT_RIGHT_CODE: u32 = 289;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PathsConfig {
     pub default_open_dir: PathBuf,
+    /// Ask before falling back to HOME when `default_open_dir` is unreachable.
+    #[serde(default = "default_true")]
+    pub prompt_if_unreachable: bool,
+    /// Where archived files go, in `YYYY/MM` subfolders. Created as
+    /// needed; `~/` is expanded.
+    #[serde(default = "default_archive_dir")]
+    pub archive_dir: PathBuf,
+    /// Retries after a transient I/O error (EIO, ETIMEDOUT, ...) while
+    /// archiving, e.g. on a stalling network share; waits
+    /// `archive_retry_delay_ms`, then twice that, and so on.
+    #[serde(default = "default_archive_retries")]
+    pub archive_retries: u32,
+    #[serde(default = "default_archive_retry_delay_ms")]
+    pub archive_retry_delay_ms: u64,
+}
+
+fn default_archive_retries() -> u32 {
+    3
+}
+
+fn default_archive_retry_delay_ms() -> u64 {
+    500
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_archive_dir() -> PathBuf {
+    UserDirs::new()
+        .and_then(|u| u.document_dir().map(Path::to_path_buf))
+        .or_else(|| std::env::var_os("HOME").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("transcribeupl-archive")
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ApplicationConfig {
     pub rewind_seconds: u32,
     pub forward_seconds: u32,
     pub hold_rewind_interval_ms: u64,
     pub play_start_rewind_seconds: u32,
+    /// Keyboard shortcut that opens the archive dialog, e.g. `"Ctrl+Shift+A"`.
+    #[serde(default = "default_archive_shortcut")]
+    pub archive_shortcut: String,
+    /// Scale float sources (e.g. 32-bit float WAV) whose peaks exceed ±1.0.
+    #[serde(default = "default_true")]
+    pub float_headroom: bool,
+    /// Pause playback when the archive dialog opens (middle pedal or button).
+    #[serde(default = "default_true")]
+    pub pause_on_archive_dialog: bool,
+    /// Output a single channel (mono mix) regardless of the source layout.
+    #[serde(default)]
+    pub force_mono_output: bool,
+    /// Route a mono mix to one ear (`left_ear`/`right_ear`) or both
+    /// (`sum`) of a stereo output, for single-ear headsets. Takes
+    /// precedence over `force_mono_output` unless `off`.
+    #[serde(default)]
+    pub mono_output: MonoMode,
+    /// Keep the source's subfolder (relative to `paths.default_open_dir`)
+    /// inside the archive, e.g. `clientA/foo.mp3` -> `archive/YYYY/MM/clientA/`.
+    #[serde(default)]
+    pub preserve_source_subpath: bool,
+    /// Desktop notification when the pedal (re)connects.
+    #[serde(default)]
+    pub notifications: bool,
+    /// Also notify on pedal disconnect (requires `notifications`).
+    #[serde(default)]
+    pub notify_on_disconnect: bool,
+    /// Back up the position by this much on every pause (reaction time).
+    #[serde(default)]
+    pub rewind_on_pause_ms: u64,
+    /// Feedback when a pedal action fires: none, visual, audible or both.
+    #[serde(default)]
+    pub pedal_feedback: PedalFeedback,
+    /// Accept files with more than two channels by folding them to stereo.
+    #[serde(default)]
+    pub downmix_surround: bool,
+    /// Show raw sample/frame indices under the progress bar.
+    #[serde(default)]
+    pub debug_readout: bool,
+    /// Keep decoded audio as `"f32"` or `"i16"`; i16 halves memory on long
+    /// files and is lossless for 16-bit sources.
+    #[serde(default)]
+    pub sample_store: SampleStorage,
+    /// Resample each file to the output device's rate once at load time
+    /// instead of leaving rate conversion to the output stage.
+    #[serde(default)]
+    pub resample_on_load: bool,
+    /// Accept `POST /play|/pause|/rewind|/archive` on 127.0.0.1 (needs the
+    /// `http-control` build feature).
+    #[serde(default)]
+    pub control_http: bool,
+    #[serde(default = "default_control_http_port")]
+    pub control_http_port: u16,
+    /// Append a SHA-256 of every archived file to `archive_ledger.jsonl`
+    /// in the data dir.
+    #[serde(default)]
+    pub archive_ledger: bool,
+    /// Review mode: never archive or otherwise touch the source files.
+    /// Sidecar files go to the data dir instead of next to the audio.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Height of the progress bar in points.
+    #[serde(default = "default_progress_bar_height")]
+    pub progress_bar_height: f32,
+    /// On archive-dialog Continue, go back to where the dialog opened
+    /// (minus `rewind_on_pause_ms`) and resume if it was playing.
+    #[serde(default)]
+    pub resume_on_continue: bool,
+    /// Length of the "loop last N seconds" quick action (L key).
+    #[serde(default = "default_loop_last_seconds")]
+    pub loop_last_seconds: u32,
+    /// Drop error banners after this many seconds; 0 keeps them until
+    /// dismissed.
+    #[serde(default)]
+    pub error_ttl_s: u64,
+    /// At launch, open the newest audio file in `paths.default_open_dir`.
+    #[serde(default)]
+    pub open_latest_on_start: bool,
+    /// Track the playback position by counting samples handed to the
+    /// output instead of estimating it from elapsed time.
+    #[serde(default = "default_true")]
+    pub sample_accurate_position: bool,
+    /// Glide to a new speed over this many ms while playing; 0 switches
+    /// instantly. Needs `sample_accurate_position` and `preserve_pitch = false`.
+    #[serde(default)]
+    pub speed_ramp_ms: u64,
+    /// Below this window width (points) the main area shows a single large
+    /// time badge instead of the full view. 0 disables the compact view.
+    #[serde(default = "default_compact_width")]
+    pub compact_width: f32,
+    /// What the compact badge shows: `remaining` or `elapsed`.
+    #[serde(default)]
+    pub compact_badge: CompactBadge,
+    /// While the archive dialog is open: right pedal = Archive, left =
+    /// Continue, holding middle = Exit.
+    #[serde(default)]
+    pub pedals_in_archive_dialog: bool,
+    /// Extra seek buttons in the top bar, in signed seconds, e.g.
+    /// `[-10, -5, -2, 5, 30]`. Zeros are ignored; at most
+    /// `MAX_QUICK_SEEKS` are shown.
+    #[serde(default)]
+    pub quick_seeks: Vec<i64>,
+    /// Short tone when playback reaches the end of the file (silent while
+    /// muted).
+    #[serde(default)]
+    pub end_beep: bool,
+    /// Base for relative seeks while playing: `live` or `committed`.
+    #[serde(default)]
+    pub seek_base: SeekBase,
+    /// Refuse to open files whose decoded audio would take more than this
+    /// many MB of memory; 0 disables the check.
+    #[serde(default = "default_max_decode_mb")]
+    pub max_decode_mb: u64,
+    /// Decode files on demand while playing, instead of all at load, when
+    /// their decoded audio would exceed this many MB. 0 never streams.
+    /// Files needing downmix, resampling, mono folding or float headroom
+    /// are always decoded up front.
+    #[serde(default = "default_stream_above_mb")]
+    pub stream_above_mb: u64,
+    /// Log play/pause/seek/speed/open/archive and pedal (dis)connects at
+    /// info level and as JSON lines in `events.jsonl` in the data dir.
+    #[serde(default)]
+    pub event_log: bool,
+    /// Keep the original pitch at speeds other than 1x (time-stretch)
+    /// instead of playing faster/slower like a tape. Disables
+    /// `speed_ramp_ms`.
+    #[serde(default = "default_true")]
+    pub preserve_pitch: bool,
+    /// Playback gain, 0.0-2.0. Saved whenever the top-bar slider is
+    /// released.
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    /// Remember where each file was paused (in `positions.json` in the
+    /// data dir) and offer to resume there when it is opened again.
+    #[serde(default = "default_true")]
+    pub resume_positions: bool,
+    /// Start with "Skip silence" on (it can be toggled in the top bar).
+    #[serde(default)]
+    pub skip_silence: bool,
+    /// Below this RMS level (dBFS) audio counts as silence.
+    #[serde(default = "default_silence_threshold_db")]
+    pub silence_threshold_db: f32,
+    /// Only quiet stretches at least this long (ms) are skipped.
+    #[serde(default = "default_silence_min_ms")]
+    pub silence_min_ms: u64,
+    /// Even out levels between recordings: `off`, `peak` or `rms`.
+    #[serde(default)]
+    pub normalize: NormalizeMode,
+    /// Start with the voice filter on (the "High-pass" checkbox).
+    #[serde(default)]
+    pub voice_filter: bool,
+    /// Cutoff of the voice filter's high-pass; 0 leaves only the presence
+    /// boost.
+    #[serde(default = "default_highpass_hz")]
+    pub highpass_hz: f32,
+    /// Add a gentle boost around 3 kHz to the voice filter.
+    #[serde(default)]
+    pub presence_boost: bool,
+    /// Fade in/out over this many ms whenever playback starts, stops or
+    /// jumps, against clicks; 0 cuts hard.
+    #[serde(default = "default_fade_ms")]
+    pub fade_ms: u64,
+    /// Prev/Next go round from the last file in the folder to the first
+    /// and back.
+    #[serde(default)]
+    pub wrap_file_navigation: bool,
+    /// Next/previous marker go round from the last marker to the first
+    /// and back.
+    #[serde(default)]
+    pub wrap_markers: bool,
+    /// After archiving, open the next file in the same folder instead of
+    /// leaving nothing loaded.
+    #[serde(default)]
+    pub advance_after_archive: bool,
+    /// Show the time left (`-MM:SS`) next to the clock, at the current
+    /// speed, i.e. how long playing to the end will actually take.
+    #[serde(default)]
+    pub show_remaining: bool,
+    /// Show the clock with milliseconds (`MM:SS.mmm`), e.g. for aligning
+    /// transcript timestamps. Display only.
+    #[serde(default)]
+    pub clock_millis: bool,
+    /// Range and step of the speed slider; clamped to 0.25..=4.0.
+    #[serde(default = "default_speed_min")]
+    pub speed_min: f32,
+    #[serde(default = "default_speed_max")]
+    pub speed_max: f32,
+    #[serde(default = "default_speed_step")]
+    pub speed_step: f32,
+    /// Markers kept per file; adding one more drops the one added longest
+    /// ago. 0 keeps them all.
+    #[serde(default = "default_max_bookmarks")]
+    pub max_bookmarks: usize,
+}
+
+pub const MAX_QUICK_SEEKS: usize = 8;
+/// Playback speeds the player accepts.
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 4.0;
+const MIN_SPEED_STEP: f32 = 0.01;
+
+impl ApplicationConfig {
+    /// `quick_seeks` without zeros or repeats, capped at `MAX_QUICK_SEEKS`.
+    pub fn quick_seek_steps(&self) -> Vec<i64> {
+        let mut steps: Vec<i64> = Vec::new();
+        for &s in &self.quick_seeks {
+            if s != 0 && !steps.contains(&s) {
+                steps.push(s);
+            }
+        }
+        if steps.len() > MAX_QUICK_SEEKS {
+            warn!(
+                "application.quick_seeks has {} entries; showing the first {}",
+                steps.len(),
+                MAX_QUICK_SEEKS
+            );
+            steps.truncate(MAX_QUICK_SEEKS);
+        }
+        steps
+    }
+
+    /// `speed_min..=speed_max` within what playback supports, and a step
+    /// that fits in it. A range narrower than the smallest step falls back
+    /// to the full one.
+    pub fn speed_range(&self) -> (RangeInclusive<f32>, f32) {
+        let lo = self.speed_min.clamp(MIN_SPEED, MAX_SPEED);
+        let hi = self.speed_max.clamp(MIN_SPEED, MAX_SPEED);
+        let (lo, hi) = if hi - lo >= MIN_SPEED_STEP {
+            (lo, hi)
+        } else {
+            (MIN_SPEED, MAX_SPEED)
+        };
+        let step = self.speed_step.clamp(MIN_SPEED_STEP, hi - lo);
+        (lo..=hi, step)
+    }
+}
+
+fn default_speed_min() -> f32 {
+    0.5
+}
+
+fn default_speed_max() -> f32 {
+    2.0
+}
+
+fn default_speed_step() -> f32 {
+    0.05
+}
+
+fn default_max_bookmarks() -> usize {
+    100
+}
+
+fn default_control_http_port() -> u16 {
+    8765
+}
+
+fn default_progress_bar_height() -> f32 {
+    18.0
+}
+
+fn default_loop_last_seconds() -> u32 {
+    5
+}
+
+fn default_compact_width() -> f32 {
+    420.0
+}
+
+fn default_silence_threshold_db() -> f32 {
+    -45.0
+}
+
+fn default_silence_min_ms() -> u64 {
+    800
+}
+
+fn default_highpass_hz() -> f32 {
+    100.0
+}
+
+fn default_fade_ms() -> u64 {
+    15
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+fn default_max_decode_mb() -> u64 {
+    4096
+}
+
+fn default_stream_above_mb() -> u64 {
+    512
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompactBadge {
+    #[default]
+    Remaining,
+    Elapsed,
+}
+
+/// Where a mono mix of the source goes on a stereo output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonoMode {
+    /// Play the source's channels as they are.
+    #[default]
+    Off,
+    LeftEar,
+    RightEar,
+    /// The mix on both channels.
+    Sum,
+}
+
+/// Loudness normalization applied as playback gain after loading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizeMode {
+    #[default]
+    Off,
+    /// Bring the loudest sample to just below full scale.
+    Peak,
+    /// Bring the average (RMS) level to a common target, without clipping.
+    Rms,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Where relative seeks (rewind/forward) count from while playing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeekBase {
+    /// The live position: samples consumed by the output when
+    /// `sample_accurate_position` is on, else the elapsed-time estimate.
+    #[default]
+    Live,
+    /// The last committed position: where playback started or was last
+    /// marked.
+    Committed,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleStorage {
+    #[default]
+    F32,
+    I16,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PedalFeedback {
+    #[default]
+    None,
+    Visual,
+    Audible,
+    Both,
+}
+
+impl PedalFeedback {
+    pub fn visual(self) -> bool {
+        matches!(self, Self::Visual | Self::Both)
+    }
+
+    pub fn audible(self) -> bool {
+        matches!(self, Self::Audible | Self::Both)
+    }
+}
+
+fn default_archive_shortcut() -> String {
+    "Ctrl+Shift+A".to_owned()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InputConfig {
     pub device_path: Option<PathBuf>,
+    /// Device given with `--device`: used instead of any other, and never
+    /// saved.
+    #[serde(skip)]
+    pub forced_device: Option<PathBuf>,
     pub selected_model: Option<String>,
+    /// Transitions on the same pedal closer together than this are
+    /// treated as switch chatter and coalesced. 0 disables.
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// Per-pedal switches; a disabled pedal's events are ignored entirely.
+    #[serde(default = "default_true")]
+    pub left_enabled: bool,
+    #[serde(default = "default_true")]
+    pub middle_enabled: bool,
+    #[serde(default = "default_true")]
+    pub right_enabled: bool,
+    /// What pressing left and right together does. Off by default.
+    #[serde(default)]
+    pub chord_left_right: ChordAction,
+    /// Max gap between the two presses for them to count as a chord.
+    #[serde(default = "default_chord_window_ms")]
+    pub chord_window_ms: u64,
+    /// Pressing the right pedal again within this long of a short tap
+    /// rewinds `double_tap_rewind_seconds` and plays, instead of the usual
+    /// pre-roll. Only when the right pedal plays (`play`/`play_pause`).
+    /// 0 disables.
+    #[serde(default)]
+    pub double_tap_window_ms: u64,
+    #[serde(default = "default_double_tap_rewind_seconds")]
+    pub double_tap_rewind_seconds: u32,
+    /// While no pedal is found, rescans happen as soon as /dev/input
+    /// changes, plus every `scan_backoff_max_ms` as a fallback. Where
+    /// change notifications aren't available, this is the rescan
+    /// interval instead, doubling after every miss up to the maximum.
+    #[serde(default = "default_scan_interval_ms")]
+    pub scan_interval_ms: u64,
+    #[serde(default = "default_scan_backoff_max_ms")]
+    pub scan_backoff_max_ms: u64,
+    /// After a read error, try to reopen the same device this many times
+    /// (waiting `reconnect_retry_ms`, then twice that, ...) before
+    /// reporting a disconnect. 0 reports it immediately.
+    #[serde(default = "default_reconnect_retries")]
+    pub reconnect_retries: u32,
+    #[serde(default = "default_reconnect_retry_ms")]
+    pub reconnect_retry_ms: u64,
+    /// Take exclusive access to the pedal while connected, so its key
+    /// presses don't also type into whatever app has focus.
+    #[serde(default = "default_true")]
+    pub grab_device: bool,
+    /// What each pedal does, by position.
+    #[serde(default)]
+    pub pedal_actions: PedalActions,
+    /// Keys that stand in for the pedals, e.g. when it fails on site.
+    #[serde(default)]
+    pub keyboard_fallback: KeyboardFallback,
+}
+
+fn default_debounce_ms() -> u64 {
+    25
+}
+
+fn default_chord_window_ms() -> u64 {
+    150
+}
+
+fn default_double_tap_rewind_seconds() -> u32 {
+    10
+}
+
+fn default_scan_interval_ms() -> u64 {
+    1000
+}
+
+fn default_scan_backoff_max_ms() -> u64 {
+    10_000
+}
+
+fn default_reconnect_retries() -> u32 {
+    3
+}
+
+fn default_reconnect_retry_ms() -> u64 {
+    150
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PedalAction {
+    /// Seek back by `play_start_rewind_seconds` and play while held;
+    /// pause on release.
+    Play,
+    /// Toggle play/pause on each press.
+    PlayPause,
+    /// While held, rewind `rewind_seconds` every `hold_rewind_interval_ms`.
+    RewindHold,
+    /// Skip ahead `forward_seconds`.
+    Forward,
+    /// Open the archive dialog.
+    Archive,
+    /// Copy the current position as `[MM:SS]` to the clipboard, for
+    /// pasting into the transcript.
+    InsertTimestamp,
+    /// Mark the current position (see Export markers).
+    InsertMarker,
+    /// Jump to the next marker after the current position.
+    NextMarker,
+    /// Jump to the marker before the current position.
+    PreviousMarker,
+    /// Do nothing (e.g. the missing button of a two-pedal model).
+    Nop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PedalActions {
+    pub left: PedalAction,
+    pub middle: PedalAction,
+    pub right: PedalAction,
+}
+
+impl Default for PedalActions {
+    fn default() -> Self {
+        Self {
+            left: PedalAction::RewindHold,
+            middle: PedalAction::Archive,
+            right: PedalAction::Play,
+        }
+    }
+}
+
+/// Keys run the same `pedal_actions` as the pedal positions, and holding
+/// one works like holding the pedal. Names as for `archive_shortcut`,
+/// without modifiers; an empty name leaves that position unmapped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyboardFallback {
+    pub enabled: bool,
+    pub left: String,
+    pub middle: String,
+    pub right: String,
+}
+
+impl Default for KeyboardFallback {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            left: "F6".to_owned(),
+            middle: "F7".to_owned(),
+            right: "F8".to_owned(),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChordAction {
+    #[default]
+    Off,
+    JumpToStart,
+    ArchiveDialog,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PedalModel {
     pub name: String,
     pub vendor_id: u16,
@@ -38,7 +615,7 @@ pub struct PedalModel {
     pub right_code: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PedalDefaults {
     pub vendor_id: u16,
     pub product_id: u16,
@@ -47,7 +624,7 @@ pub struct PedalDefaults {
     pub right_code: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     pub paths: PathsConfig,
     pub application: ApplicationConfig,
@@ -55,6 +632,16 @@ pub struct Config {
     pub pedal_defaults: PedalDefaults,
     #[serde(default)]
     pub pedals: Vec<PedalModel>,
+    #[serde(default)]
+    pub audio: AudioConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioConfig {
+    /// Output device by name (as listed in Settings); the system default
+    /// when unset or not found.
+    #[serde(default)]
+    pub output_device: Option<String>,
 }
 
 impl Default for Config {
@@ -64,16 +651,90 @@ impl Default for Config {
                 default_open_dir: PathBuf::from(
                     "/run/user/1000/gvfs/smb-share:server=100.99.88.66,share=daten/diktat",
                 ),
+                prompt_if_unreachable: true,
+                archive_dir: default_archive_dir(),
+                archive_retries: default_archive_retries(),
+                archive_retry_delay_ms: default_archive_retry_delay_ms(),
             },
             application: ApplicationConfig {
                 rewind_seconds: 3,
                 forward_seconds: 3,
                 hold_rewind_interval_ms: 500,
                 play_start_rewind_seconds: 1,
+                archive_shortcut: default_archive_shortcut(),
+                float_headroom: true,
+                pause_on_archive_dialog: true,
+                force_mono_output: false,
+                mono_output: MonoMode::Off,
+                preserve_source_subpath: false,
+                notifications: false,
+                notify_on_disconnect: false,
+                rewind_on_pause_ms: 0,
+                pedal_feedback: PedalFeedback::None,
+                downmix_surround: false,
+                debug_readout: false,
+                sample_store: SampleStorage::F32,
+                resample_on_load: false,
+                control_http: false,
+                control_http_port: default_control_http_port(),
+                archive_ledger: false,
+                read_only: false,
+                progress_bar_height: default_progress_bar_height(),
+                resume_on_continue: false,
+                loop_last_seconds: default_loop_last_seconds(),
+                error_ttl_s: 0,
+                open_latest_on_start: false,
+                sample_accurate_position: true,
+                speed_ramp_ms: 0,
+                compact_width: default_compact_width(),
+                compact_badge: CompactBadge::Remaining,
+                pedals_in_archive_dialog: false,
+                quick_seeks: Vec::new(),
+                end_beep: false,
+                seek_base: SeekBase::Live,
+                max_decode_mb: default_max_decode_mb(),
+                stream_above_mb: default_stream_above_mb(),
+                event_log: false,
+                preserve_pitch: true,
+                volume: default_volume(),
+                resume_positions: true,
+                skip_silence: false,
+                silence_threshold_db: default_silence_threshold_db(),
+                silence_min_ms: default_silence_min_ms(),
+                normalize: NormalizeMode::Off,
+                voice_filter: false,
+                highpass_hz: default_highpass_hz(),
+                presence_boost: false,
+                fade_ms: default_fade_ms(),
+                wrap_file_navigation: false,
+                wrap_markers: false,
+                advance_after_archive: false,
+                show_remaining: false,
+                clock_millis: false,
+                speed_min: default_speed_min(),
+                speed_max: default_speed_max(),
+                speed_step: default_speed_step(),
+                max_bookmarks: default_max_bookmarks(),
             },
             input: InputConfig {
                 device_path: None,
+                forced_device: None,
                 selected_model: None,
+                debounce_ms: default_debounce_ms(),
+                left_enabled: true,
+                middle_enabled: true,
+                right_enabled: true,
+                chord_left_right: ChordAction::Off,
+                chord_window_ms: default_chord_window_ms(),
+                double_tap_window_ms: 0,
+                double_tap_rewind_seconds: default_double_tap_rewind_seconds(),
+                scan_interval_ms: default_scan_interval_ms(),
+                scan_backoff_max_ms: default_scan_backoff_max_ms(),
+                reconnect_retries: default_reconnect_retries(),
+                reconnect_retry_ms: default_reconnect_retry_ms(),
+                grab_device: true,
+                pedal_actions: PedalActions::default(),
+                keyboard_fallback: KeyboardFallback::default(),
             },
             pedal_defaults: PedalDefaults {
                 vendor_id: DEFAULT_VENDOR_ID,
@@ -83,12 +744,55 @@ impl Default for Config {
                 right_code: DEFAULT_RIGHT_CODE,
             },
             pedals: vec![],
+            audio: AudioConfig::default(),
         }
     }
 }
 
+/// Use `path` as the config file for this process (`--config`). Call
+/// before anything reads the config.
+pub fn init_config_path(path: PathBuf) {
+    if CONFIG_OVERRIDE.set(path).is_err() {
+        warn!("Config path already set; ignoring override");
+    }
+}
+
+/// Decide whether to run portable (`--portable`, or a `portable.txt` next to
+/// the executable) and log the base dir config is read from.
+pub fn init_portable(flag: bool) {
+    let dir = PORTABLE_DIR.get_or_init(|| detect_portable_dir(flag));
+    match dir {
+        Some(d) => info!("Portable mode: base dir {}", d.display()),
+        None => info!(
+            "Base dir: {}",
+            Config::config_path()
+                .parent()
+                .unwrap_or(Path::new("."))
+                .display()
+        ),
+    }
+}
+
+fn portable_dir() -> Option<&'static Path> {
+    PORTABLE_DIR
+        .get_or_init(|| detect_portable_dir(false))
+        .as_deref()
+}
+
+fn detect_portable_dir(flag: bool) -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let dir = exe.parent()?.to_path_buf();
+    (flag || dir.join(PORTABLE_MARKER).is_file()).then_some(dir)
+}
+
 impl Config {
     pub fn config_path() -> PathBuf {
+        if let Some(path) = CONFIG_OVERRIDE.get() {
+            return path.clone();
+        }
+        if let Some(dir) = portable_dir() {
+            return dir.join("config.toml");
+        }
         if let Some(pd) = ProjectDirs::from("com", "transcribeupl", "transcribeupl") {
             let path = pd.config_dir().to_path_buf();
             std::fs::create_dir_all(&path).ok();
@@ -103,6 +807,23 @@ impl Config {
         path.join("config.toml")
     }
 
+    /// Directory for app data such as the archive ledger (next to the
+    /// executable in portable mode).
+    pub fn data_dir() -> PathBuf {
+        let path = if let Some(dir) = portable_dir() {
+            dir.to_path_buf()
+        } else if let Some(pd) = ProjectDirs::from("com", "transcribeupl", "transcribeupl") {
+            pd.data_dir().to_path_buf()
+        } else {
+            std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join(".local/share/transcribeupl")
+        };
+        std::fs::create_dir_all(&path).ok();
+        path
+    }
+
     pub fn load_or_default() -> Self {
         let path = Self::config_path();
         match fs::read_to_string(&path) {
@@ -127,13 +848,61 @@ impl Config {
         }
     }
 
+    /// Re-read the config file after it changed on disk. `None` (logged)
+    /// if it can't be read or parsed, so the caller keeps what it has.
+    pub fn reload() -> Option<Self> {
+        let path = Self::config_path();
+        let s = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Failed to read config at {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        match toml::from_str(&s) {
+            Ok(cfg) => {
+                info!("Reloaded config from {}", path.display());
+                Some(cfg)
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to parse config at {}: {}. Keeping the current settings.",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
     pub fn save(&self) -> anyhow::Result<()> {
         let path = Self::config_path();
         let s = toml::to_string_pretty(self)?;
-        fs::write(&path, s)?;
+        write_atomic(&path, s.as_bytes())?;
         Ok(())
     }
 
+    /// Copy an existing config file to `config.toml.bak`, replacing any
+    /// older backup. The original stays in place until it is overwritten,
+    /// so a failed save still leaves a config behind. Returns the backup
+    /// path if there was a file to copy.
+    pub fn backup_existing() -> anyhow::Result<Option<PathBuf>> {
+        let path = Self::config_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut bak = path.clone().into_os_string();
+        bak.push(".bak");
+        let bak = PathBuf::from(bak);
+        fs::copy(&path, &bak)?;
+        Ok(Some(bak))
+    }
+
+    /// Whether the configured open dir currently exists (e.g. the share is mounted).
+    pub fn default_open_dir_reachable(&self) -> bool {
+        self.paths.default_open_dir.is_dir()
+    }
+
     pub fn resolve_default_open_dir(&self) -> PathBuf {
         let p = &self.paths.default_open_dir;
         if p.exists() && p.is_dir() {
@@ -149,3 +918,24 @@ impl Config {
         std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_speed_range_narrower_than_a_step_falls_back_to_the_full_range() {
+        let mut app = Config::default().application;
+        app.speed_min = 1.0;
+        app.speed_max = 1.005;
+        let (range, step) = app.speed_range();
+        assert_eq!(range, MIN_SPEED..=MAX_SPEED);
+        assert_eq!(step, app.speed_step);
+
+        app.speed_max = 1.02;
+        app.speed_step = 0.1;
+        let (range, step) = app.speed_range();
+        assert_eq!(range, 1.0..=1.02);
+        assert!((step - 0.02).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,77 @@
+//! Reloads `config.toml` when it changes on disk, so hand edits take
+//! effect without a restart.
+
+use crate::config::Config;
+use crate::inotify::DirWatch;
+use eframe::egui;
+use log::{debug, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// How long a wait may last before re-checking shutdown.
+const POLL_TIMEOUT: Duration = Duration::from_millis(250);
+// Editors may write the file in several steps; reload once they're done.
+const SETTLE: Duration = Duration::from_millis(200);
+
+pub struct ConfigWatcher {
+    rx: Receiver<Config>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl ConfigWatcher {
+    /// Watch the config file's directory (the file itself may be replaced
+    /// by a rename). `None` (logged) if that isn't possible.
+    pub fn start(ctx: egui::Context) -> Option<Self> {
+        let path = Config::config_path();
+        let name = path.file_name()?.to_os_string();
+        let dir = path.parent()?;
+        let watch = match DirWatch::new(dir, libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) {
+            Ok(w) => w,
+            Err(e) => {
+                warn!("Cannot watch {} for changes: {}", dir.display(), e);
+                return None;
+            }
+        };
+        let (tx, rx) = mpsc::channel();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let stop = shutdown.clone();
+        let spawned = thread::Builder::new()
+            .name("config-watch".into())
+            .spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    if !watch.wait(POLL_TIMEOUT) || !watch.read_names().contains(&name) {
+                        continue;
+                    }
+                    while watch.wait(SETTLE) {
+                        watch.read_names();
+                    }
+                    debug!("Config file changed");
+                    if let Some(cfg) = Config::reload() {
+                        if tx.send(cfg).is_err() {
+                            return;
+                        }
+                        ctx.request_repaint();
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("Failed to spawn config watcher: {}", e);
+            return None;
+        }
+        Some(Self { rx, shutdown })
+    }
+
+    /// The most recent valid config read since the last call, if any.
+    pub fn latest(&self) -> Option<Config> {
+        self.rx.try_iter().last()
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,208 @@
+//! Optional local HTTP endpoint mapping `POST /play`, `/pause`, `/rewind` and
+//! `/archive` onto the same actions as the pedal. Only compiled in with the
+//! `http-control` feature and only started when `application.control_http`
+//! is set; it always binds to 127.0.0.1.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCommand {
+    Play,
+    Pause,
+    Rewind,
+    Archive,
+}
+
+impl ControlCommand {
+    #[cfg_attr(not(feature = "http-control"), allow(dead_code))]
+    fn from_path(path: &str) -> Option<Self> {
+        match path.trim_end_matches('/') {
+            "/play" => Some(ControlCommand::Play),
+            "/pause" => Some(ControlCommand::Pause),
+            "/rewind" => Some(ControlCommand::Rewind),
+            "/archive" => Some(ControlCommand::Archive),
+            _ => None,
+        }
+    }
+}
+
+/// Status line and command for a raw request head. Browsers attach an
+/// `Origin` header to every cross-site POST, so any request carrying one is
+/// refused: otherwise any web page could drive the player through
+/// 127.0.0.1. Local tools (curl, a switch daemon) don't send it.
+#[cfg_attr(not(feature = "http-control"), allow(dead_code))]
+fn route(request: &str) -> (&'static str, Option<ControlCommand>) {
+    let mut lines = request.lines();
+    let mut parts = lines.next().unwrap_or("").split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+    let from_browser = lines
+        .take_while(|l| !l.is_empty())
+        .filter_map(|l| l.split_once(':'))
+        .any(|(name, _)| name.trim().eq_ignore_ascii_case("origin"));
+
+    match ControlCommand::from_path(path) {
+        _ if from_browser => ("403 Forbidden", None),
+        Some(cmd) if method == "POST" => ("204 No Content", Some(cmd)),
+        Some(_) => ("405 Method Not Allowed", None),
+        None => ("404 Not Found", None),
+    }
+}
+
+#[cfg(feature = "http-control")]
+pub use server::ControlServer;
+
+#[cfg(feature = "http-control")]
+mod server {
+    use super::{route, ControlCommand};
+    use anyhow::{Context, Result};
+    use log::{info, warn};
+    use std::io::{Read, Write};
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::Sender;
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    const ACCEPT_POLL: Duration = Duration::from_millis(50);
+    const READ_TIMEOUT: Duration = Duration::from_secs(1);
+    const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+    pub struct ControlServer {
+        pub port: u16,
+        shutdown: Arc<AtomicBool>,
+        handle: Option<JoinHandle<()>>,
+    }
+
+    impl ControlServer {
+        pub fn start(port: u16, tx: Sender<ControlCommand>, ctx: egui::Context) -> Result<Self> {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+                .with_context(|| format!("Failed to bind 127.0.0.1:{}", port))?;
+            // Non-blocking accept so the thread can notice shutdown.
+            listener.set_nonblocking(true)?;
+            info!("HTTP control listening on 127.0.0.1:{}", port);
+
+            let shutdown = Arc::new(AtomicBool::new(false));
+            let flag = shutdown.clone();
+            let handle = thread::spawn(move || {
+                while !flag.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Some(cmd) = handle_connection(stream) {
+                                if tx.send(cmd).is_err() {
+                                    break;
+                                }
+                                ctx.request_repaint();
+                            }
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                            thread::sleep(ACCEPT_POLL);
+                        }
+                        Err(e) => {
+                            warn!("HTTP control accept failed: {}", e);
+                            thread::sleep(ACCEPT_POLL);
+                        }
+                    }
+                }
+            });
+
+            Ok(Self {
+                port,
+                shutdown,
+                handle: Some(handle),
+            })
+        }
+    }
+
+    impl Drop for ControlServer {
+        fn drop(&mut self) {
+            // Join so the port is free again if the server is restarted.
+            self.shutdown.store(true, Ordering::Relaxed);
+            if let Some(handle) = self.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    /// Read one request, answer it and return the command it carried.
+    fn handle_connection(mut stream: TcpStream) -> Option<ControlCommand> {
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 1024];
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_BYTES {
+            match stream.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+
+        let (status, cmd) = route(&String::from_utf8_lossy(&buf));
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        );
+        cmd
+    }
+}
+
+/// Stand-in when built without the feature, so enabling it in the config
+/// reports an error instead of silently doing nothing.
+#[cfg(not(feature = "http-control"))]
+pub struct ControlServer {
+    pub port: u16,
+}
+
+#[cfg(not(feature = "http-control"))]
+impl ControlServer {
+    pub fn start(
+        _port: u16,
+        _tx: std::sync::mpsc::Sender<ControlCommand>,
+        _ctx: egui::Context,
+    ) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!("built without the `http-control` feature"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post_runs_the_command() {
+        let req = "POST /play HTTP/1.1\r\nHost: 127.0.0.1:8765\r\n\r\n";
+        assert_eq!(route(req), ("204 No Content", Some(ControlCommand::Play)));
+        let req = "POST /archive/ HTTP/1.1\r\n\r\n";
+        assert_eq!(
+            route(req),
+            ("204 No Content", Some(ControlCommand::Archive))
+        );
+    }
+
+    #[test]
+    fn other_methods_and_paths_are_refused() {
+        assert_eq!(
+            route("GET /play HTTP/1.1\r\n\r\n"),
+            ("405 Method Not Allowed", None)
+        );
+        assert_eq!(
+            route("POST /stop HTTP/1.1\r\n\r\n"),
+            ("404 Not Found", None)
+        );
+    }
+
+    #[test]
+    fn requests_from_a_web_page_are_refused() {
+        for origin in ["Origin: https://example.com", "origin: null", "ORIGIN:x"] {
+            let req = format!("POST /play HTTP/1.1\r\nHost: 127.0.0.1\r\n{origin}\r\n\r\n");
+            assert_eq!(route(&req), ("403 Forbidden", None), "{origin}");
+        }
+    }
+
+    #[test]
+    fn origin_in_the_body_is_not_a_header() {
+        let req = "POST /pause HTTP/1.1\r\nContent-Length: 9\r\n\r\nOrigin: x";
+        assert_eq!(route(req), ("204 No Content", Some(ControlCommand::Pause)));
+    }
+}
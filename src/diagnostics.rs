@@ -0,0 +1,137 @@
+//! Pedal diagnostics: the raw events the pedal sends and the input
+//! devices present, for working out what an unfamiliar pedal emits.
+
+use crate::pedal::{input_devices, InputDeviceInfo, PedalEvent, PedalStatus};
+use chrono::{DateTime, Local};
+use eframe::egui;
+use std::collections::VecDeque;
+
+// Events kept for the window; older ones are dropped.
+const RECENT_EVENTS: usize = 50;
+
+struct LoggedEvent {
+    at: DateTime<Local>,
+    code: u32,
+    value: i32,
+}
+
+/// Recent events are recorded whether or not the window is open, so the
+/// presses that "did nothing" are already there when it is.
+pub struct PedalDiagnostics {
+    events: VecDeque<LoggedEvent>,
+    devices: Option<Vec<InputDeviceInfo>>, // listed when first shown
+}
+
+impl PedalDiagnostics {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            devices: None,
+        }
+    }
+
+    /// Record `ev` as received, before any mapping or filtering.
+    pub fn record(&mut self, ev: &PedalEvent) {
+        if self.events.len() >= RECENT_EVENTS {
+            self.events.pop_front();
+        }
+        let age = chrono::Duration::from_std(ev.time.elapsed()).unwrap_or_default();
+        self.events.push_back(LoggedEvent {
+            at: Local::now() - age,
+            code: ev.code,
+            value: ev.value,
+        });
+    }
+
+    /// `codes` are the left/middle/right codes currently in effect.
+    pub fn ui(&mut self, ui: &mut egui::Ui, status: &PedalStatus, codes: [u32; 3]) {
+        ui.heading("Device");
+        match status {
+            PedalStatus::Connected {
+                name,
+                path,
+                vendor_id,
+                product_id,
+            } => {
+                ui.label(format!("{} @ {}", name, path.display()));
+                ui.monospace(format!("VID {:04x}  PID {:04x}", vendor_id, product_id));
+            }
+            PedalStatus::Scanning => {
+                ui.label("Scanning…");
+            }
+            PedalStatus::NotFound { .. } => {
+                ui.label("No pedal matching the configured models or device_path");
+            }
+            PedalStatus::Error(e) => {
+                ui.label(format!("Error: {}", e));
+            }
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.heading("Recent events");
+            if ui.button("Clear").clicked() {
+                self.events.clear();
+            }
+        });
+        if self.events.is_empty() {
+            ui.weak("Press a pedal; its events appear here.");
+        }
+        egui::ScrollArea::vertical()
+            .id_source("diag_events")
+            .max_height(200.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                egui::Grid::new("diag_events_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for ev in &self.events {
+                            ui.monospace(ev.at.format("%H:%M:%S%.3f").to_string());
+                            ui.monospace(format!("code {}", ev.code));
+                            ui.monospace(match ev.value {
+                                0 => "release",
+                                1 => "press",
+                                2 => "repeat",
+                                _ => "?",
+                            });
+                            ui.label(match codes.iter().position(|c| *c == ev.code) {
+                                Some(0) => "left",
+                                Some(1) => "middle",
+                                Some(2) => "right",
+                                _ => "(unmapped)",
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.heading("Input devices");
+            if ui.button("Rescan").clicked() {
+                self.devices = None;
+            }
+        });
+        let devices = self.devices.get_or_insert_with(input_devices);
+        if devices.is_empty() {
+            ui.weak("None readable; check permissions on /dev/input.");
+        }
+        egui::ScrollArea::vertical()
+            .id_source("diag_devices")
+            .max_height(160.0)
+            .show(ui, |ui| {
+                egui::Grid::new("diag_devices_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for d in devices.iter() {
+                            ui.monospace(format!("{:04x}:{:04x}", d.vendor_id, d.product_id));
+                            ui.label(&d.name);
+                            ui.monospace(d.path.display().to_string());
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
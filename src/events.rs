@@ -0,0 +1,139 @@
+//! Optional log of playback state changes for external monitoring
+//! (`application.event_log`). Each event is logged at info level under the
+//! `events` target and appended as a JSON line to `events.jsonl` in the
+//! data dir.
+
+use crate::audio::PlaybackSnapshot;
+use crate::config::Config;
+use log::{info, warn};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const EVENTS_FILE: &str = "events.jsonl";
+
+// A position further than this from where steady playback would have put
+// it counts as a seek.
+const SEEK_TOLERANCE_SECS: f64 = 0.5;
+
+#[derive(Debug, Serialize)]
+struct EventEntry<'a> {
+    ts: String,
+    event: &'a str,
+    file: Option<&'a Path>,
+    position_s: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<&'a str>,
+}
+
+/// What the last frame looked like, to detect transitions.
+struct Seen {
+    file: Option<PathBuf>,
+    playing: bool,
+    speed: f32,
+    position_secs: f64,
+    at: Instant,
+}
+
+pub struct EventLog {
+    file: Option<File>,
+    seen: Option<Seen>,
+}
+
+impl EventLog {
+    pub fn open() -> Self {
+        let path = Config::data_dir().join(EVENTS_FILE);
+        let file = std::fs::create_dir_all(Config::data_dir())
+            .and_then(|_| OpenOptions::new().create(true).append(true).open(&path));
+        let file = match file {
+            Ok(f) => Some(f),
+            Err(e) => {
+                warn!(
+                    "Event log {} unavailable ({}); logging only",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        };
+        Self { file, seen: None }
+    }
+
+    /// Record a one-off event such as "archived" or "pedal_connected".
+    pub fn record(&mut self, event: &str, snap: &PlaybackSnapshot, detail: Option<&str>) {
+        let entry = EventEntry {
+            ts: chrono::Local::now().to_rfc3339(),
+            event,
+            file: snap.file_path.as_deref(),
+            position_s: (snap.position_secs * 1000.0).round() / 1000.0,
+            detail,
+        };
+        info!(
+            target: "events",
+            "{} file={} pos={:.3}s{}",
+            event,
+            entry.file.map(|p| p.display().to_string()).unwrap_or_default(),
+            entry.position_s,
+            detail.map(|d| format!(" {}", d)).unwrap_or_default()
+        );
+        let Some(f) = &mut self.file else {
+            return;
+        };
+        let Ok(mut line) = serde_json::to_string(&entry) else {
+            return;
+        };
+        line.push('\n');
+        if let Err(e) = f.write_all(line.as_bytes()) {
+            warn!("Event log write failed: {}", e);
+            self.file = None;
+        }
+    }
+
+    /// Compare with the previous frame and record opens, play/pause, seeks
+    /// and speed changes, whatever triggered them. While `looping`, jumps
+    /// are the loop wrapping and aren't reported as seeks.
+    pub fn observe(&mut self, snap: &PlaybackSnapshot, looping: bool) {
+        let now = Instant::now();
+        if let Some(prev) = self.seen.take() {
+            if prev.file != snap.file_path {
+                let event = if snap.file_path.is_some() {
+                    "opened"
+                } else {
+                    "closed"
+                };
+                self.record(event, snap, None);
+            } else if snap.loaded {
+                if prev.playing != snap.playing {
+                    let event = if snap.playing { "play" } else { "pause" };
+                    self.record(event, snap, None);
+                }
+                if (prev.speed - snap.speed).abs() > f32::EPSILON {
+                    let detail = format!("speed={:.2}", snap.speed);
+                    self.record("speed", snap, Some(&detail));
+                }
+                let expected = if prev.playing && snap.playing {
+                    prev.position_secs + (now - prev.at).as_secs_f64() * prev.speed as f64
+                } else {
+                    prev.position_secs
+                };
+                // Reaching the end or pausing may move the position a bit.
+                if !looping
+                    && prev.playing == snap.playing
+                    && (snap.position_secs - expected).abs() > SEEK_TOLERANCE_SECS
+                {
+                    let detail = format!("from={:.3}s", prev.position_secs);
+                    self.record("seek", snap, Some(&detail));
+                }
+            }
+        }
+        self.seen = Some(Seen {
+            file: snap.file_path.clone(),
+            playing: snap.playing,
+            speed: snap.speed,
+            position_secs: snap.position_secs,
+            at: now,
+        });
+    }
+}
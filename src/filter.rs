@@ -0,0 +1,105 @@
+//! Voice clean-up filter for playback: a high-pass against rumble and an
+//! optional presence boost, as biquads (RBJ cookbook) with per-channel
+//! state.
+
+use std::f32::consts::TAU;
+
+// Presence boost: a gentle peak where consonants live.
+const PRESENCE_HZ: f32 = 3000.0;
+const PRESENCE_DB: f32 = 4.0;
+const PRESENCE_Q: f32 = 1.0;
+// Butterworth response for the high-pass.
+const HIGHPASS_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    fn highpass(rate: f32, hz: f32, q: f32) -> Self {
+        let w0 = TAU * hz / rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 + cos) / 2.0 / a0,
+            b1: -(1.0 + cos) / a0,
+            b2: (1.0 + cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+
+    fn peaking(rate: f32, hz: f32, q: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = TAU * hz / rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a0 = 1.0 + alpha / a;
+        Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * cos / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+}
+
+/// Transposed direct form II state of one biquad on one channel.
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    z1: f32,
+    z2: f32,
+}
+
+impl State {
+    #[inline]
+    fn run(&mut self, f: &Biquad, x: f32) -> f32 {
+        let y = f.b0 * x + self.z1;
+        self.z1 = f.b1 * x - f.a1 * y + self.z2;
+        self.z2 = f.b2 * x - f.a2 * y;
+        y
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VoiceFilter {
+    stages: Vec<Biquad>,
+    state: Vec<State>, // channels * stages
+}
+
+impl VoiceFilter {
+    /// High-pass at `highpass_hz` (skipped when 0), plus the presence
+    /// boost if `presence`. `rate` is the content sample rate.
+    pub fn new(rate: u32, channels: usize, highpass_hz: f32, presence: bool) -> Self {
+        let rate = rate as f32;
+        let nyquist = rate / 2.0;
+        let mut stages = Vec::new();
+        if highpass_hz > 0.0 && highpass_hz < nyquist {
+            stages.push(Biquad::highpass(rate, highpass_hz, HIGHPASS_Q));
+        }
+        if presence && PRESENCE_HZ < nyquist {
+            stages.push(Biquad::peaking(rate, PRESENCE_HZ, PRESENCE_Q, PRESENCE_DB));
+        }
+        Self {
+            state: vec![State::default(); channels.max(1) * stages.len()],
+            stages,
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, channel: usize, x: f32) -> f32 {
+        let n = self.stages.len();
+        let state = &mut self.state[channel * n..(channel + 1) * n];
+        self.stages
+            .iter()
+            .zip(state)
+            .fold(x, |v, (f, s)| s.run(f, v))
+    }
+}
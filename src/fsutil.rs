@@ -0,0 +1,207 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replace `path` with `contents` without ever leaving a truncated file:
+/// write a temp file in the same directory, fsync it, then rename it over
+/// the target. A crash leaves either the old or the new file intact.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = dir.join(tmp_name);
+
+    let res = (|| {
+        let mut f = File::create(&tmp)?;
+        f.write_all(contents)?;
+        f.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return res;
+    }
+
+    // Persist the rename itself; best-effort since not all filesystems allow it.
+    if let Ok(d) = File::open(dir) {
+        let _ = d.sync_all();
+    }
+    Ok(())
+}
+
+/// Most recently modified file directly in `dir` whose extension is one of
+/// `extensions` (case-insensitive). `None` if the dir is empty or unreadable.
+pub fn newest_file_with_ext(dir: &Path, extensions: &[&str]) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .filter(|e| has_ext(&e.path(), extensions))
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .max_by_key(|(mtime, _)| *mtime)
+        .map(|(_, path)| path)
+}
+
+/// Files directly in `dir` with one of `extensions` (case-insensitive), in
+/// natural order (`take2` before `take10`).
+pub fn files_with_ext(dir: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|rd| {
+            rd.flatten()
+                .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+                .map(|e| e.path())
+                .filter(|p| has_ext(p, extensions))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort_by(|a, b| natural_cmp(&file_name_lossy(a), &file_name_lossy(b)));
+    files
+}
+
+/// The file before (`forward = false`) or after `current` among its
+/// siblings with one of `extensions`, wrapping around the ends if `wrap`.
+/// `current` itself needn't exist any more (e.g. after archiving).
+pub fn sibling_file(
+    current: &Path,
+    extensions: &[&str],
+    forward: bool,
+    wrap: bool,
+) -> Option<PathBuf> {
+    let dir = current.parent()?;
+    let files = files_with_ext(dir, extensions);
+    let name = file_name_lossy(current);
+    // Index of the first file sorting after `current`, skipping itself.
+    let after = files
+        .iter()
+        .position(|f| natural_cmp(&file_name_lossy(f), &name).is_gt())
+        .unwrap_or(files.len());
+    let before = files
+        .iter()
+        .rposition(|f| natural_cmp(&file_name_lossy(f), &name).is_lt());
+    let picked = if forward {
+        files.get(after).or(wrap.then(|| files.first()).flatten())
+    } else {
+        before
+            .map(|i| &files[i])
+            .or(wrap.then(|| files.last()).flatten())
+    };
+    picked.filter(|p| p.as_path() != current).cloned()
+}
+
+fn has_ext(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| extensions.iter().any(|want| x.eq_ignore_ascii_case(want)))
+}
+
+fn file_name_lossy(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Case-insensitive comparison that orders runs of digits by value.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_num = |it: &mut std::iter::Peekable<std::str::Chars<'_>>| {
+                    let mut digits = String::new();
+                    while let Some(c) = it.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (da, db) = (take_num(&mut a), take_num(&mut b));
+                let (ta, tb) = (da.trim_start_matches('0'), db.trim_start_matches('0'));
+                let ord = ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb));
+                if ord.is_ne() {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ord = x.to_lowercase().cmp(y.to_lowercase());
+                if ord.is_ne() {
+                    return ord;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn dir_entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn write_atomic_creates_and_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        write_atomic(&path, b"first").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        write_atomic(&path, b"second, longer").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second, longer");
+        write_atomic(&path, b"3").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"3");
+        // No temp file left behind.
+        assert_eq!(dir_entries(dir.path()), ["config.toml"]);
+    }
+
+    #[test]
+    fn write_atomic_swaps_the_file_instead_of_rewriting_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, b"old contents").unwrap();
+        // A reader that opened the old file keeps seeing it whole: the new
+        // contents arrive as a different file renamed into place.
+        let mut reader = File::open(&path).unwrap();
+        write_atomic(&path, b"new").unwrap();
+        let mut seen = String::new();
+        reader.read_to_string(&mut seen).unwrap();
+        assert_eq!(seen, "old contents");
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+    }
+
+    #[test]
+    fn write_atomic_failure_leaves_the_target_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        // Renaming a file over a non-empty directory fails.
+        let path = dir.path().join("sidecar");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), b"kept").unwrap();
+        assert!(write_atomic(&path, b"new").is_err());
+        assert_eq!(fs::read(path.join("keep")).unwrap(), b"kept");
+        assert_eq!(dir_entries(dir.path()), ["sidecar"]);
+    }
+
+    #[test]
+    fn write_atomic_needs_a_file_name() {
+        assert!(write_atomic(Path::new("/"), b"x").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Minimal inotify wrapper for watching one directory.
+
+use std::ffi::{CString, OsString};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+use std::time::Duration;
+
+// Fixed part of `struct inotify_event`: wd, mask, cookie, len.
+const EVENT_HEADER_LEN: usize = 16;
+
+pub struct DirWatch {
+    fd: OwnedFd,
+}
+
+impl DirWatch {
+    /// Watch `dir` for the `IN_*` events in `mask`.
+    pub fn new(dir: &Path, mask: u32) -> io::Result<Self> {
+        // SAFETY: plain syscall; a non-negative return is a new fd we own.
+        let raw = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `raw` was just returned by inotify_init1 and nothing
+        // else owns it.
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+        let path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: `path` is NUL-terminated and `fd` is valid.
+        let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd })
+    }
+
+    /// Wait up to `timeout` for events. `false` on timeout.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let mut pfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `pfd` is a valid pollfd for the duration of the call.
+        let rc = unsafe { libc::poll(&mut pfd, 1, timeout.as_millis().max(1) as i32) };
+        rc > 0
+    }
+
+    /// Take all queued events, returning the names they are about.
+    pub fn read_names(&self) -> Vec<OsString> {
+        let mut names = Vec::new();
+        // Aligned for `inotify_event`, and enough for one with a max-length name.
+        let mut buf = [0u32; 1024];
+        loop {
+            // SAFETY: `buf` is writable for its full length; the fd is
+            // non-blocking, so this stops with EAGAIN once empty.
+            let n = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr().cast(),
+                    std::mem::size_of_val(&buf),
+                )
+            };
+            if n <= 0 {
+                break;
+            }
+            // SAFETY: the first `n` bytes were just written by read().
+            let bytes =
+                unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), n as usize) };
+            let mut off = 0;
+            while off + EVENT_HEADER_LEN <= bytes.len() {
+                let len_bytes = &bytes[off + 12..off + EVENT_HEADER_LEN];
+                let len = u32::from_ne_bytes(len_bytes.try_into().expect("4 bytes")) as usize;
+                let start = off + EVENT_HEADER_LEN;
+                let end = (start + len).min(bytes.len());
+                // The name is NUL-padded.
+                let name = &bytes[start..end];
+                let name = name.split(|b| *b == 0).next().unwrap_or_default();
+                if !name.is_empty() {
+                    names.push(OsString::from_vec(name.to_vec()));
+                }
+                off = start + len;
+            }
+        }
+        names
+    }
+}
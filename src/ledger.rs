@@ -0,0 +1,152 @@
+use crate::config::Config;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use log::{error, info};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+const LEDGER_FILE: &str = "archive_ledger.jsonl";
+
+/// One line of the append-only archive ledger. `source` and
+/// `destination` are as moved: a restore goes from the archive back to
+/// the original location.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum LedgerEntry {
+    Archived {
+        archived_at: String,
+        source: PathBuf,
+        destination: PathBuf,
+        bytes: u64,
+        sha256: String,
+    },
+    Restored {
+        restored_at: String,
+        source: PathBuf,
+        destination: PathBuf,
+        bytes: u64,
+        sha256: String,
+    },
+}
+
+pub fn ledger_path() -> PathBuf {
+    Config::data_dir().join(LEDGER_FILE)
+}
+
+/// Append a ledger entry for a file archived with `sha256` (hex, and its
+/// length). The hash is the source's, taken by the archive worker before
+/// the move.
+pub fn record_archive(
+    src: PathBuf,
+    dest: PathBuf,
+    archived_at: DateTime<Local>,
+    (sha256, bytes): (String, u64),
+) {
+    append_in_background(LedgerEntry::Archived {
+        archived_at: archived_at.to_rfc3339(),
+        source: src,
+        destination: dest,
+        bytes,
+        sha256,
+    });
+}
+
+/// Append a ledger entry for an archived file moved back to `original`.
+pub fn record_restore(
+    archived: PathBuf,
+    original: PathBuf,
+    restored_at: DateTime<Local>,
+    (sha256, bytes): (String, u64),
+) {
+    append_in_background(LedgerEntry::Restored {
+        restored_at: restored_at.to_rfc3339(),
+        source: archived,
+        destination: original,
+        bytes,
+        sha256,
+    });
+}
+
+/// Append on a worker thread, so a slow disk doesn't stall the UI.
+fn append_in_background(entry: LedgerEntry) {
+    let spawned = thread::Builder::new().name("ledger".into()).spawn(move || {
+        if let Err(e) = append_entry(&entry) {
+            error!("Archive ledger entry failed: {:#}", e);
+        }
+    });
+    if let Err(e) = spawned {
+        error!("Failed to spawn ledger thread: {}", e);
+    }
+}
+
+fn append_entry(entry: &LedgerEntry) -> Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    let path = ledger_path();
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open ledger {}", path.display()))?;
+    // One write per line keeps concurrent appends from interleaving.
+    f.write_all(line.as_bytes())?;
+    f.sync_data()?;
+    info!("Ledger: {}", line.trim_end());
+    Ok(())
+}
+
+/// Hex SHA-256 and length of the file at `path`.
+pub fn sha256_file(path: &Path) -> Result<(String, u64)> {
+    let mut f = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut total = 0u64;
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_tagged_with_their_event() {
+        let entry = LedgerEntry::Restored {
+            restored_at: "2024-06-03T14:05:09+02:00".into(),
+            source: PathBuf::from("/arc/2024/06/memo_20240603_140509.mp3"),
+            destination: PathBuf::from("/in/memo.mp3"),
+            bytes: 5,
+            sha256: "ab".into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"event":"restored","restored_at":"2024-06-03T14:05:09+02:00","source":"/arc/2024/06/memo_20240603_140509.mp3","destination":"/in/memo.mp3","bytes":5,"sha256":"ab"}"#
+        );
+    }
+
+    #[test]
+    fn sha256_of_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memo.mp3");
+        std::fs::write(&path, b"audio").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            (
+                "6ed8919ce20490a5e3ad8630a4fab69475297abd07db73918dd5f36fcfaeb11b".to_owned(),
+                5
+            )
+        );
+    }
+}
@@ -1,23 +1,102 @@
+mod archive;
 mod audio;
+mod cli;
 mod config;
+mod config_watch;
+mod control;
+mod diagnostics;
+mod events;
+mod filter;
+mod fsutil;
+mod inotify;
+mod keys;
+mod ledger;
+mod markers;
+mod notification;
 mod pedal;
+mod positions;
+mod probe;
+mod settings;
+mod stream;
+mod stretch;
 mod ui_time;
-
-use crate::audio::Player;
-use crate::config::Config;
+mod waveform;
+
+use crate::archive::{
+    archive_in_background, restore_in_background, ArchiveOptions, ArchiveResult, Archived,
+    RestoreResult,
+};
+use crate::audio::{
+    decode_to_f32_interleaved, DecodeOptions, DecodeProgress, DecodedAudio, PlaybackSnapshot,
+    Player, UnsupportedChannels,
+};
+use crate::cli::CliArgs;
+use crate::config::{ChordAction, CompactBadge, Config, PedalAction, SeekBase};
+use crate::config_watch::ConfigWatcher;
+use crate::control::{ControlCommand, ControlServer};
+use crate::diagnostics::PedalDiagnostics;
+use crate::events::EventLog;
+use crate::fsutil::{newest_file_with_ext, sibling_file, write_atomic};
+use crate::keys::{parse_key, parse_shortcut};
+use crate::ledger::{record_archive, record_restore};
+use crate::markers::{timestamp_list, write_sidecar, Markers};
+use crate::notification::desktop_notify;
 use crate::pedal::{PedalEvent, PedalManager, PedalMsg, PedalStatus};
-use crate::ui_time::format_clock;
+use crate::positions::PositionStore;
+use crate::settings::SettingsForm;
+use crate::ui_time::{
+    format_clock, format_clock_ms, format_remaining, format_span, format_span_ms,
+};
+use crate::waveform::Waveform;
 
-use chrono::{Datelike, Local};
 use eframe::egui;
 use egui::Color32;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use rfd::FileDialog;
+use std::collections::HashMap;
 use std::io::Write;
-use std::path::PathBuf;
-use std::sync::mpsc;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread;
 use std::time::{Duration, Instant};
 
+// How long a control stays highlighted after its pedal action fires.
+const CUE_FLASH: Duration = Duration::from_millis(250);
+const CUE_FLASH_COLOR: Color32 = Color32::from_rgb(40, 110, 200);
+// File types offered by the Open dialogs and picked up from folders.
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "wav", "ogg", "opus", "flac", "m4a", "aac"];
+// Quick-pick speeds in the Speed menu, limited to the configured range.
+const SPEED_PRESETS: [f32; 6] = [0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+const SPEED_BADGE_COLOR: Color32 = Color32::from_rgb(255, 170, 0);
+const COMPACT_BADGE_SIZE: f32 = 48.0;
+// End-of-file beep (`application.end_beep`)
+const END_BEEP_FREQ: f32 = 660.0;
+const END_BEEP_LEN: Duration = Duration::from_millis(250);
+// Holding middle this long in the archive dialog means Exit.
+const DIALOG_EXIT_HOLD: Duration = Duration::from_millis(1000);
+const MARKER_COLOR: Color32 = Color32::from_rgb(230, 60, 60);
+// Jumping back skips a marker less than this behind the position.
+const MARKER_SLACK_SECS: f64 = 0.5;
+const WAVEFORM_HEIGHT: f32 = 48.0;
+
+/// Pedal actions that get visual/audible feedback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cue {
+    Play,
+    Rewind,
+    Archive,
+}
+
+impl Cue {
+    fn freq(self) -> f32 {
+        match self {
+            Cue::Play => 880.0,
+            Cue::Rewind => 660.0,
+            Cue::Archive => 440.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct UiError {
     id: u64,
@@ -25,6 +104,12 @@ struct UiError {
     ts: Instant,
 }
 
+struct DecodeJob {
+    path: PathBuf,
+    progress: Arc<DecodeProgress>,
+    rx: mpsc::Receiver<anyhow::Result<DecodedAudio>>,
+}
+
 struct App {
     cfg: Config,
     player: Player,
@@ -32,15 +117,40 @@ struct App {
     // UI state
     errors: Vec<UiError>,
     next_err_id: u64,
+    // `application.event_log`
+    event_log: Option<EventLog>,
+    // File being decoded on a worker thread
+    decode_job: Option<DecodeJob>,
+    // `application.resume_positions`
+    positions: Option<PositionStore>,
+    // Stored position offered once after opening a file
+    resume_prompt: Option<f64>,
+    // Playing last frame; a pause saves the position
+    was_playing: bool,
+    // File that failed only for its channel count, offered as "Open as mono"
+    // for as long as its error (by id) is shown
+    mono_retry: Option<(u64, PathBuf)>,
 
     // Pedal
     pedal_status: PedalStatus,
     pedal_rx: mpsc::Receiver<PedalMsg>,
-    _pedal_mgr: PedalManager,
+    // None when something else feeds pedal_rx, as in tests
+    pedal_mgr: Option<PedalManager>,
     // Tracking pressed state for debounce
     left_pressed: bool,
     right_pressed: bool,
     middle_pressed: bool,
+    // Chatter filtering: last accepted transition and held-back bounces per code
+    last_transition: HashMap<u32, Instant>,
+    bounced: HashMap<u32, PedalEvent>,
+    // Press times for left+right chord detection
+    left_press_at: Option<Instant>,
+    right_press_at: Option<Instant>,
+    // Position and play state before the last left/right press, restored
+    // if that press turns out to be the first half of a chord
+    chord_undo: Option<(usize, bool)>,
+    // End of the last short right-pedal tap, while a double tap may follow
+    right_tap_end: Option<Instant>,
     // Codes (current mapping in effect)
     left_code: u32,
     right_code: u32,
@@ -49,63 +159,149 @@ struct App {
     // Repeated rewind
     hold_last_tick: Option<Instant>,
 
+    // Pedal feedback
+    flash: Option<(Cue, Instant)>,
+
+    // Open dir unreachable prompt
+    show_open_dir_prompt: bool,
+    open_dir_still_unreachable: bool,
+
     // Archive dialog
     show_archive_dialog: bool,
+    archive_shortcut: Option<egui::KeyboardShortcut>,
+    // Keyboard stand-ins for the left/middle/right pedal, and which are down
+    fallback_keys: [Option<egui::Key>; 3],
+    fallback_down: [bool; 3],
+    // Validated `application.quick_seeks`
+    quick_seeks: Vec<i64>,
+    // Play state before a progress-bar drag, restored on release
+    scrub_resume: Option<bool>,
     archive_error: Option<String>,
     archive_pending_exit: bool,
+    // Resume-position key of the file being archived, dropped on success
+    archive_position_key: Option<String>,
+    // Positions marked in the loaded file, sorted
+    markers: Markers,
+    // Overview of the loaded file; None while streamed or nothing loaded
+    waveform: Option<Waveform>,
+    // Last archive this session as (source, destination), for Undo
+    last_archive: Option<(PathBuf, PathBuf)>,
+    // Running archive worker; the file stays loaded until it reports back
+    archive_job: Option<mpsc::Receiver<ArchiveResult>>,
+    // Running Undo archive worker, and whether to quit once it is done
+    restore_job: Option<mpsc::Receiver<RestoreResult>>,
+    restore_pending_exit: bool,
+    // Position and play state when the dialog opened, for Continue
+    archive_resume: Option<(usize, bool)>,
+    // Middle pressed while the dialog was open (hold = Exit)
+    dialog_middle_down: Option<Instant>,
+
+    // Config reset confirmation
+    show_reset_config_confirm: bool,
+
+    // Settings window, while open
+    settings: Option<SettingsForm>,
+    pedal_diag: PedalDiagnostics,
+    show_pedal_diag: bool,
+
+    // Optional HTTP remote control
+    egui_ctx: egui::Context,
+    control_rx: mpsc::Receiver<ControlCommand>,
+    control: Option<ControlServer>,
+    // Picks up hand edits to config.toml
+    config_watcher: Option<ConfigWatcher>,
 
     // Quit
     request_close: bool,
 }
 
 impl App {
-    fn new(cc: &eframe::CreationContext<'_>, cfg: Config) -> Self {
+    fn new(cc: &eframe::CreationContext<'_>, cfg: Config, args: CliArgs) -> Self {
         cc.egui_ctx.set_pixels_per_point(1.0);
 
         // Audio player
-        let player = Player::new().expect("Audio output init failed");
+        let mut player =
+            Player::new(cfg.audio.output_device.as_deref()).expect("Audio output init failed");
+        configure_player(&mut player, &cfg);
 
         // Logging initial
         info!("App start");
 
-        // Pedal manager
         let (tx, rx) = mpsc::channel::<PedalMsg>();
         let mgr = PedalManager::start(cfg.clone(), tx);
+        let mut app = Self::with_player(cfg, player, rx, cc.egui_ctx.clone());
+        app.pedal_mgr = Some(mgr);
+        app.config_watcher = ConfigWatcher::start(cc.egui_ctx.clone());
+        app.sync_control_server();
+
+        if args.stdin {
+            let opts = DecodeOptions::from_config(&app.cfg.application);
+            match app.player.load_stdin(&opts) {
+                Ok(()) => {
+                    info!("Loaded audio from stdin");
+                    app.start_waveform();
+                }
+                Err(e) => app.push_error(format!("Reading stdin failed: {}", e)),
+            }
+        } else if let Some(path) = &args.file {
+            // Failures show in the error banner like any other open.
+            app.open_path(path);
+        } else if app.cfg.application.open_latest_on_start {
+            // Missing or empty folder: just start with nothing loaded.
+            let dir = app.cfg.paths.default_open_dir.clone();
+            match newest_file_with_ext(&dir, AUDIO_EXTENSIONS) {
+                Some(path) => app.open_path(&path),
+                None => info!("No audio file to open in {}", dir.display()),
+            }
+        }
+
+        app
+    }
 
+    /// The app around `player`, reading pedal input from `pedal_rx`, with
+    /// nothing opened yet and none of the pedal manager, config watcher and
+    /// control server started.
+    fn with_player(
+        cfg: Config,
+        player: Player,
+        pedal_rx: mpsc::Receiver<PedalMsg>,
+        egui_ctx: egui::Context,
+    ) -> Self {
         // Codes from defaults or selected model
-        let (l, m, r) = if let Some(name) = &cfg.input.selected_model {
-            if let Some(model) = cfg.pedals.iter().find(|p| &p.name == name) {
-                (model.left_code, model.middle_code, model.right_code)
-            } else {
-                warn!("Selected model '{}' not found; using defaults", name);
-                (
-                    cfg.pedal_defaults.left_code,
-                    cfg.pedal_defaults.middle_code,
-                    cfg.pedal_defaults.right_code,
-                )
-            }
-        } else {
-            (
-                cfg.pedal_defaults.left_code,
-                cfg.pedal_defaults.middle_code,
-                cfg.pedal_defaults.right_code,
-            )
-        };
+        let (l, m, r) = pedal_codes(&cfg);
+
+        let archive_shortcut = archive_shortcut(&cfg);
+        let fallback_keys = fallback_keys(&cfg);
+        let quick_seeks = cfg.application.quick_seek_steps();
+        let event_log = cfg.application.event_log.then(EventLog::open);
+        let positions = cfg.application.resume_positions.then(PositionStore::load);
 
         Self {
             cfg,
             player,
 
             errors: Vec::new(),
+            event_log,
+            decode_job: None,
+            positions,
+            resume_prompt: None,
+            was_playing: false,
+            mono_retry: None,
             next_err_id: 1,
 
             pedal_status: PedalStatus::Scanning,
-            pedal_rx: rx,
-            _pedal_mgr: mgr,
+            pedal_rx,
+            pedal_mgr: None,
 
             left_pressed: false,
             right_pressed: false,
             middle_pressed: false,
+            last_transition: HashMap::new(),
+            bounced: HashMap::new(),
+            left_press_at: None,
+            right_press_at: None,
+            chord_undo: None,
+            right_tap_end: None,
 
             left_code: l,
             right_code: r,
@@ -113,15 +309,264 @@ impl App {
 
             hold_last_tick: None,
 
+            flash: None,
+
+            show_open_dir_prompt: false,
+            open_dir_still_unreachable: false,
+
             show_archive_dialog: false,
+            archive_shortcut,
+            fallback_keys,
+            fallback_down: [false; 3],
+            quick_seeks,
+            scrub_resume: None,
             archive_error: None,
             archive_pending_exit: false,
+            archive_position_key: None,
+            last_archive: None,
+            markers: Markers::default(),
+            waveform: None,
+            archive_job: None,
+            restore_job: None,
+            restore_pending_exit: false,
+            archive_resume: None,
+            dialog_middle_down: None,
+
+            show_reset_config_confirm: false,
+            settings: None,
+            pedal_diag: PedalDiagnostics::new(),
+            show_pedal_diag: false,
+
+            egui_ctx,
+            control_rx: mpsc::channel().1,
+            control: None,
+            config_watcher: None,
 
             request_close: false,
         }
     }
 
+    /// Apply a config file edited by hand. Our own saves come back here
+    /// too, and are skipped as unchanged.
+    fn poll_config_reload(&mut self) {
+        let Some(mut cfg) = self.config_watcher.as_ref().and_then(ConfigWatcher::latest) else {
+            return;
+        };
+        // As apply_config would, so our own saves compare equal.
+        cfg.input.forced_device = self.cfg.input.forced_device.clone();
+        if cfg != self.cfg {
+            info!("Config changed on disk; applying");
+            self.apply_config(cfg);
+        }
+    }
+
+    /// Swap in a new config and re-derive everything that depends on it:
+    /// player options, pedal codes, shortcuts and the pedal manager.
+    fn apply_config(&mut self, mut cfg: Config) {
+        // Command-line overrides outlive config changes.
+        cfg.input.forced_device = self.cfg.input.forced_device.clone();
+        let device_changed = cfg.audio.output_device != self.cfg.audio.output_device;
+        let pedal_changed = cfg.input != self.cfg.input
+            || cfg.pedals != self.cfg.pedals
+            || cfg.pedal_defaults != self.cfg.pedal_defaults;
+        self.cfg = cfg;
+        if device_changed {
+            if let Err(e) = self
+                .player
+                .set_output_device(self.cfg.audio.output_device.as_deref())
+            {
+                self.push_error(format!("Switching audio output failed: {}", e));
+            }
+        }
+        configure_player(&mut self.player, &self.cfg);
+        (self.left_code, self.middle_code, self.right_code) = pedal_codes(&self.cfg);
+        self.archive_shortcut = archive_shortcut(&self.cfg);
+        self.fallback_keys = fallback_keys(&self.cfg);
+        self.fallback_down = [false; 3];
+        if self.cfg.application.event_log != self.event_log.is_some() {
+            self.event_log = self.cfg.application.event_log.then(EventLog::open);
+        }
+        if self.cfg.application.resume_positions != self.positions.is_some() {
+            self.positions = self
+                .cfg
+                .application
+                .resume_positions
+                .then(PositionStore::load);
+        }
+        self.quick_seeks = self.cfg.application.quick_seek_steps();
+        self.markers.prune(self.cfg.application.max_bookmarks);
+        self.sync_control_server();
+
+        // Restart pedal detection with the new device preferences; left
+        // alone otherwise, so a held pedal isn't dropped.
+        if !pedal_changed {
+            return;
+        }
+        if self.pedal_mgr.is_some() {
+            let (tx, rx) = mpsc::channel::<PedalMsg>();
+            self.pedal_mgr = Some(PedalManager::start(self.cfg.clone(), tx));
+            self.pedal_rx = rx;
+        }
+        self.pedal_status = PedalStatus::Scanning;
+        self.left_pressed = false;
+        self.right_pressed = false;
+        self.middle_pressed = false;
+        self.hold_last_tick = None;
+        self.right_tap_end = None;
+        self.last_transition.clear();
+        self.bounced.clear();
+    }
+
+    /// Start, stop or rebind the HTTP control server to match the config.
+    fn sync_control_server(&mut self) {
+        let app = &self.cfg.application;
+        let wanted = app.control_http.then_some(app.control_http_port);
+        if wanted == self.control.as_ref().map(|c| c.port) {
+            return;
+        }
+        self.control = None;
+        let Some(port) = wanted else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel::<ControlCommand>();
+        match ControlServer::start(port, tx, self.egui_ctx.clone()) {
+            Ok(server) => {
+                self.control = Some(server);
+                self.control_rx = rx;
+            }
+            Err(e) => self.push_error(format!("HTTP control unavailable: {:#}", e)),
+        }
+    }
+
+    /// Remote commands map onto the same actions as the pedal.
+    fn drain_control_cmds(&mut self) {
+        while let Ok(cmd) = self.control_rx.try_recv() {
+            info!("Remote control: {:?}", cmd);
+            match cmd {
+                ControlCommand::Play => {
+                    if !self.player.playing {
+                        if self.player.loop_region.is_none() {
+                            let back = -(self.cfg.application.play_start_rewind_seconds as i64);
+                            self.player.seek_seconds(back);
+                        }
+                        self.player.play_from_current();
+                        self.pedal_cue(Cue::Play);
+                    }
+                }
+                ControlCommand::Pause => self.player.pause(),
+                ControlCommand::Rewind => {
+                    let back = -(self.cfg.application.rewind_seconds as i64);
+                    self.player.seek_seconds(back);
+                    self.pedal_cue(Cue::Rewind);
+                }
+                ControlCommand::Archive => {
+                    if self.player.file_path.is_some() {
+                        self.open_archive_dialog();
+                        if self.show_archive_dialog {
+                            self.pedal_cue(Cue::Archive);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn reset_config_to_defaults(&mut self) -> anyhow::Result<()> {
+        if let Some(bak) = Config::backup_existing()? {
+            info!("Previous config backed up to {}", bak.display());
+        }
+        let cfg = Config::default();
+        cfg.save()?;
+        info!("Config reset to defaults");
+        self.apply_config(cfg);
+        Ok(())
+    }
+
+    fn ui_reset_config_dialog(&mut self, ctx: &egui::Context) {
+        if !self.show_reset_config_confirm {
+            return;
+        }
+        egui::Window::new("Reset config")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("Replace the current configuration with the defaults?");
+                ui.label(format!(
+                    "The current file is kept as {}.bak",
+                    Config::config_path().display()
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Reset").clicked() {
+                        self.show_reset_config_confirm = false;
+                        if let Err(e) = self.reset_config_to_defaults() {
+                            self.push_error(format!("Reset config failed: {}", e));
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.show_reset_config_confirm = false;
+                    }
+                });
+            });
+    }
+
+    fn ui_settings_window(&mut self, ctx: &egui::Context) {
+        let Some(form) = &mut self.settings else {
+            return;
+        };
+        let models: Vec<String> = self.cfg.pedals.iter().map(|m| m.name.clone()).collect();
+        let mut open = true;
+        let mut apply = false;
+        let mut close = false;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                form.ui(ui, &models);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    apply = ui.button("Apply").clicked();
+                    close = ui.button("Close").clicked();
+                });
+            });
+        if apply {
+            if let Some(cfg) = form.apply_to(&self.cfg) {
+                match cfg.save() {
+                    Ok(()) => {
+                        info!("Settings saved");
+                        self.apply_config(cfg);
+                    }
+                    Err(e) => self.push_error(format!("Saving settings failed: {}", e)),
+                }
+            }
+        }
+        if !open || close {
+            self.settings = None;
+        }
+    }
+
+    fn ui_pedal_diagnostics(&mut self, ctx: &egui::Context) {
+        if !self.show_pedal_diag {
+            return;
+        }
+        let codes = [self.left_code, self.middle_code, self.right_code];
+        let mut open = true;
+        egui::Window::new("Pedal diagnostics")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                self.pedal_diag.ui(ui, &self.pedal_status, codes);
+            });
+        self.show_pedal_diag = open;
+    }
+
     fn push_error(&mut self, msg: impl Into<String>) {
+        self.push_error_id(msg);
+    }
+
+    /// [`Self::push_error`], returning the error's id.
+    fn push_error_id(&mut self, msg: impl Into<String>) -> u64 {
         let id = self.next_err_id;
         self.next_err_id += 1;
         let msg = msg.into();
@@ -131,19 +576,120 @@ impl App {
             msg,
             ts: Instant::now(),
         });
+        id
+    }
+
+    /// Drop errors by id, along with a retry offered for one of them.
+    fn remove_errors(&mut self, ids: &[u64]) {
+        self.errors.retain(|e| !ids.contains(&e.id));
+        if self
+            .mono_retry
+            .as_ref()
+            .is_some_and(|(id, _)| ids.contains(id))
+        {
+            self.mono_retry = None;
+        }
+    }
+
+    /// Ask once whether to continue where `path` was last left, unless that
+    /// was (nearly) the start or the end.
+    fn offer_resume(&mut self, path: &Path) {
+        self.resume_prompt = None;
+        let Some(secs) = self.positions.as_ref().and_then(|p| p.get(path)) else {
+            return;
+        };
+        let duration = self.player.snapshot().duration_secs;
+        if secs > 1.0 && secs < duration - 1.0 {
+            self.resume_prompt = Some(secs);
+        }
+    }
+
+    /// Remember the position of the loaded file (on pause and on exit).
+    fn save_position(&mut self) {
+        let snap = self.player.snapshot();
+        if let (Some(store), Some(path)) = (&mut self.positions, &snap.file_path) {
+            store.set(path, snap.position_secs);
+        }
+    }
+
+    fn track_pause_for_resume(&mut self) {
+        let playing = self.player.playing;
+        if self.was_playing && !playing {
+            self.save_position();
+        }
+        self.was_playing = playing;
+    }
+
+    fn ui_resume_prompt(&mut self, ctx: &egui::Context) {
+        let Some(secs) = self.resume_prompt else {
+            return;
+        };
+        egui::Window::new("Resume")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!("Resume at {}?", format_span(secs.floor() as u64)));
+                ui.horizontal(|ui| {
+                    if ui.button("Resume").clicked() {
+                        self.resume_prompt = None;
+                        self.seek_to_secs(secs);
+                    }
+                    if ui.button("Start over").clicked() {
+                        self.resume_prompt = None;
+                    }
+                });
+            });
+    }
+
+    /// Persist the current volume so it survives restarts.
+    fn save_volume(&mut self) {
+        self.cfg.application.volume = self.player.volume;
+        if let Err(e) = self.cfg.save() {
+            self.push_error(format!("Saving volume failed: {}", e));
+        }
+    }
+
+    fn log_event(&mut self, event: &str, detail: Option<&str>) {
+        if let Some(log) = &mut self.event_log {
+            log.record(event, &self.player.snapshot(), detail);
+        }
+    }
+
+    fn expire_errors(&mut self) {
+        let ttl = self.cfg.application.error_ttl_s;
+        if ttl > 0 {
+            let ttl = Duration::from_secs(ttl);
+            let expired: Vec<u64> = self
+                .errors
+                .iter()
+                .filter(|e| e.ts.elapsed() >= ttl)
+                .map(|e| e.id)
+                .collect();
+            if !expired.is_empty() {
+                self.remove_errors(&expired);
+            }
+        }
     }
 
     fn drain_pedal_msgs(&mut self) {
         while let Ok(msg) = self.pedal_rx.try_recv() {
             match msg {
                 PedalMsg::Status(s) => {
+                    let was_connected = matches!(self.pedal_status, PedalStatus::Connected { .. });
                     self.pedal_status = s.clone();
                     match &s {
-                        PedalStatus::Connected { name, path } => {
+                        PedalStatus::Connected { name, path, .. } => {
                             info!("Pedal connected: {} @ {}", name, path.display());
+                            if !was_connected {
+                                self.log_event("pedal_connected", Some(name));
+                            }
+                            if !was_connected && self.cfg.application.notifications {
+                                desktop_notify("Pedal connected", name.clone());
+                            }
                         }
                         PedalStatus::Scanning => {}
-                        PedalStatus::NotFound => {}
+                        PedalStatus::NotFound { .. } => {}
                         PedalStatus::Error(e) => {
                             self.push_error(format!("Pedal error: {}", e));
                         }
@@ -153,8 +699,15 @@ impl App {
                     // Pause playback immediately
                     self.player.pause();
                     self.push_error("Pedal disconnected");
+                    self.log_event("pedal_disconnected", None);
+                    if self.cfg.application.notifications
+                        && self.cfg.application.notify_on_disconnect
+                    {
+                        desktop_notify("Pedal disconnected", "Playback paused".to_owned());
+                    }
                 }
                 PedalMsg::Input(ev) => {
+                    self.pedal_diag.record(&ev);
                     self.handle_pedal_event(ev);
                 }
             }
@@ -167,127 +720,715 @@ impl App {
             return;
         }
         let is_press = ev.value == 1;
-        let code = ev.code;
-
-        if code == self.right_code {
-            // Debounce
-            if is_press && !self.right_pressed {
-                self.right_pressed = true;
-                // RightPress: seek back by play_start_rewind_seconds and start playback
-                let back = -(self.cfg.application.play_start_rewind_seconds as i64);
-                self.player.seek_seconds(back);
-                self.player.play_from_current();
-            } else if !is_press && self.right_pressed {
-                self.right_pressed = false;
-                // RightRelease: pause immediately
-                self.player.pause();
+        if !self.pedal_enabled(ev.code) {
+            if is_press {
+                info!("Ignoring code {} from disabled pedal", ev.code);
             }
             return;
         }
+        let Some(pressed) = self.pedal_pressed_state(ev.code) else {
+            return;
+        };
+        if is_press == pressed {
+            // Back where we are: any bounce in between has settled.
+            self.bounced.remove(&ev.code);
+            return;
+        }
 
-        if code == self.left_code {
-            if is_press && !self.left_pressed {
-                self.left_pressed = true;
-                self.hold_last_tick = Some(Instant::now());
-                // No immediate seek; first action occurs after interval.
-            } else if !is_press && self.left_pressed {
-                self.left_pressed = false;
-                self.hold_last_tick = None;
+        // Switch chatter: a transition too soon after the previous accepted
+        // one on the same code is held back and only applied if the state
+        // still differs once the debounce window has passed.
+        let window = Duration::from_millis(self.cfg.input.debounce_ms);
+        if let Some(prev) = self.last_transition.get(&ev.code) {
+            if ev.time.saturating_duration_since(*prev) < window {
+                debug!("Pedal chatter on code {} (value {})", ev.code, ev.value);
+                self.bounced.insert(ev.code, ev);
+                return;
+            }
+        }
+        self.bounced.remove(&ev.code);
+        self.last_transition.insert(ev.code, ev.time);
+        self.apply_pedal(ev.code, is_press, ev.time);
+    }
+
+    /// Apply held-back chatter transitions whose debounce window has passed
+    /// by `now`.
+    fn settle_bounced_pedals(&mut self, now: Instant) {
+        let window = Duration::from_millis(self.cfg.input.debounce_ms);
+        let settled: Vec<u32> = self
+            .bounced
+            .keys()
+            .copied()
+            .filter(|code| {
+                self.last_transition
+                    .get(code)
+                    .map(|t| now.saturating_duration_since(*t) >= window)
+                    .unwrap_or(true)
+            })
+            .collect();
+        for code in settled {
+            if let Some(ev) = self.bounced.remove(&code) {
+                let is_press = ev.value == 1;
+                if self.pedal_pressed_state(code) != Some(is_press) {
+                    self.last_transition.insert(code, now);
+                    self.apply_pedal(code, is_press, now);
+                }
             }
-            return;
         }
+    }
 
-        if code == self.middle_code {
-            if is_press && !self.middle_pressed {
-                self.middle_pressed = true;
-                // MiddlePress: pause playback and open archive dialog
-                self.player.pause();
-                self.show_archive_dialog = true;
-            } else if !is_press && self.middle_pressed {
-                self.middle_pressed = false;
+    /// Fallback keys go through `apply_pedal` like the device, so holds,
+    /// chords and the archive dialog behave the same. Only their own
+    /// transitions count, so a working pedal isn't released by them.
+    fn poll_keyboard_pedals(&mut self, ctx: &egui::Context) {
+        let codes = [self.left_code, self.middle_code, self.right_code];
+        for (i, code) in codes.into_iter().enumerate() {
+            let Some(key) = self.fallback_keys[i] else {
+                continue;
+            };
+            let down = ctx.input(|inp| inp.key_down(key));
+            if down != self.fallback_down[i] {
+                self.fallback_down[i] = down;
+                self.apply_pedal(code, down, Instant::now());
             }
-            return;
         }
     }
 
-    fn tick_hold_rewind(&mut self) {
-        if !self.left_pressed {
+    fn pedal_pressed_state(&self, code: u32) -> Option<bool> {
+        if code == self.right_code {
+            Some(self.right_pressed)
+        } else if code == self.left_code {
+            Some(self.left_pressed)
+        } else if code == self.middle_code {
+            Some(self.middle_pressed)
+        } else {
+            None
+        }
+    }
+
+    fn pedal_enabled(&self, code: u32) -> bool {
+        let input = &self.cfg.input;
+        if code == self.left_code {
+            input.left_enabled
+        } else if code == self.middle_code {
+            input.middle_enabled
+        } else if code == self.right_code {
+            input.right_enabled
+        } else {
+            true
+        }
+    }
+
+    fn apply_pedal(&mut self, code: u32, is_press: bool, at: Instant) {
+        if self.show_archive_dialog && self.cfg.application.pedals_in_archive_dialog {
+            self.apply_pedal_in_dialog(code, is_press);
             return;
         }
-        let Some(last) = self.hold_last_tick else {
+        if is_press && self.try_chord(code, at) {
+            return;
+        }
+
+        let double_tap = code == self.right_code && self.right_double_tap(is_press, at);
+
+        // Track which button is down, whatever it is mapped to.
+        let actions = self.cfg.input.pedal_actions;
+        let (pressed, action) = if code == self.right_code {
+            if is_press {
+                self.right_press_at = Some(at);
+            }
+            (&mut self.right_pressed, actions.right)
+        } else if code == self.left_code {
+            if is_press {
+                self.left_press_at = Some(at);
+            }
+            (&mut self.left_pressed, actions.left)
+        } else if code == self.middle_code {
+            (&mut self.middle_pressed, actions.middle)
+        } else {
             return;
         };
-        let interval = Duration::from_millis(self.cfg.application.hold_rewind_interval_ms);
-        if last.elapsed() >= interval {
-            // Rewind while playing continues
-            let back = -(self.cfg.application.rewind_seconds as i64);
+        if *pressed == is_press {
+            return;
+        }
+        *pressed = is_press;
+        if is_press
+            && code != self.middle_code
+            && self.cfg.input.chord_left_right != ChordAction::Off
+        {
+            self.chord_undo = Some((self.player.current_index_interleaved(), self.player.playing));
+        }
+
+        if double_tap && matches!(action, PedalAction::Play | PedalAction::PlayPause) {
+            // Further back than the pre-roll; the release still pauses
+            // as usual when holding to play.
+            let back = -(self.cfg.input.double_tap_rewind_seconds as i64);
             self.player.seek_seconds(back);
-            self.hold_last_tick = Some(Instant::now());
+            self.player.play_from_current();
+            self.pedal_cue(Cue::Rewind);
+        } else if is_press {
+            self.pedal_action_press(action);
+        } else {
+            self.pedal_action_release(action);
         }
     }
 
-    fn ui_top_bar(&mut self, ui: &mut egui::Ui) {
-        // Buttons: Open, Play/Pause, Rewind, Forward, Speed dropdown, Archive
-        if ui.button("Open").clicked() {
-            let start_dir = self.cfg.resolve_default_open_dir();
-            if let Some(path) = FileDialog::new()
-                .set_directory(start_dir)
-                .add_filter("Audio", &["mp3", "wav", "ogg", "opus"])
-                .pick_file()
-            {
-                match self.player.load_file(&path) {
-                    Ok(()) => {
-                        info!("Opened file: {}", path.display());
-                    }
-                    Err(e) => {
-                        self.push_error(format!("Open failed: {}", e));
-                    }
-                }
-            }
+    /// Whether this right-pedal press is the second half of a double tap.
+    /// The first tap has already acted on its own, so nothing waits for
+    /// the window to pass; a long press-and-hold doesn't count as a tap.
+    fn right_double_tap(&mut self, is_press: bool, at: Instant) -> bool {
+        let window = Duration::from_millis(self.cfg.input.double_tap_window_ms);
+        if window.is_zero() || is_press == self.right_pressed {
+            return false;
         }
+        if is_press {
+            return self
+                .right_tap_end
+                .take()
+                .is_some_and(|end| at.saturating_duration_since(end) <= window);
+        }
+        let held = self
+            .right_press_at
+            .map(|p| at.saturating_duration_since(p))
+            .unwrap_or(Duration::MAX);
+        self.right_tap_end = (held <= window).then_some(at);
+        false
+    }
 
-        let can_control = self.player.audio.is_some();
-
-        if ui
-            .add_enabled(
-                can_control,
-                egui::Button::new(if self.player.playing { "Pause" } else { "Play" }),
-            )
-            .clicked()
-        {
-            if self.player.playing {
-                self.player.pause();
-            } else {
+    fn pedal_action_press(&mut self, action: PedalAction) {
+        match action {
+            PedalAction::Play => {
+                // Seek back by play_start_rewind_seconds and start playback
+                // (no pre-roll inside a loop, since seeking would clear it)
+                if self.player.loop_region.is_none() {
+                    let back = -(self.cfg.application.play_start_rewind_seconds as i64);
+                    self.player.seek_seconds(back);
+                }
                 self.player.play_from_current();
+                self.pedal_cue(Cue::Play);
+            }
+            PedalAction::PlayPause => {
+                if self.player.playing {
+                    self.player.pause();
+                } else {
+                    self.player.play_from_current();
+                    self.pedal_cue(Cue::Play);
+                }
             }
+            PedalAction::RewindHold => {
+                // No immediate seek; first action occurs after interval.
+                self.hold_last_tick = Some(Instant::now());
+            }
+            PedalAction::Forward => {
+                self.player
+                    .seek_seconds(self.cfg.application.forward_seconds as i64);
+            }
+            PedalAction::Archive => {
+                // Open archive dialog (pausing unless configured not to)
+                self.open_archive_dialog();
+                if self.show_archive_dialog {
+                    self.pedal_cue(Cue::Archive);
+                }
+            }
+            PedalAction::InsertTimestamp => self.copy_timestamp(),
+            PedalAction::InsertMarker => self.insert_marker(),
+            PedalAction::NextMarker => self.jump_to_marker(true),
+            PedalAction::PreviousMarker => self.jump_to_marker(false),
+            PedalAction::Nop => {}
         }
+    }
 
-        if ui
-            .add_enabled(can_control, egui::Button::new("Rewind"))
-            .clicked()
-        {
-            let back = -(self.cfg.application.rewind_seconds as i64);
-            self.player.seek_seconds(back);
+    fn pedal_action_release(&mut self, action: PedalAction) {
+        match action {
+            PedalAction::Play => self.player.pause(),
+            PedalAction::RewindHold => self.hold_last_tick = None,
+            _ => {}
         }
+    }
 
-        if ui
-            .add_enabled(can_control, egui::Button::new("Forward"))
-            .clicked()
-        {
-            self.player
-                .seek_seconds(self.cfg.application.forward_seconds as i64);
+    fn seek_to_secs(&mut self, secs: f64) {
+        if let Some(audio) = &self.player.audio {
+            let frame = (secs * audio.sample_rate as f64) as usize;
+            let idx = frame * audio.channels as usize;
+            self.player.seek_to_index(idx);
         }
+    }
 
-        ui.separator();
+    fn insert_marker(&mut self) {
+        let snap = self.player.snapshot();
+        if !snap.loaded {
+            return;
+        }
+        let at = Duration::from_secs_f64(snap.position_secs);
+        if let Some(i) = self.markers.insert(at, self.cfg.application.max_bookmarks) {
+            info!("Marker {} at {}", i + 1, format_span_ms(snap.position_secs));
+        }
+    }
 
-        egui::ComboBox::from_label("Speed")
-            .selected_text(format!("{:.2}x", self.player.speed))
-            .show_ui(ui, |ui| {
-                for s in [0.75_f32, 1.0, 1.25, 1.5] {
-                    if ui
-                        .selectable_label(
-                            (self.player.speed - s).abs() < 1e-3,
-                            format!("{:.2}x", s),
+    /// Jump to the next marker, or the previous one. Going back skips a
+    /// marker just passed so repeated presses keep moving while playing.
+    /// With `wrap_markers` the last marker leads on to the first and back.
+    fn jump_to_marker(&mut self, forward: bool) {
+        let pos = self.player.snapshot().position_secs;
+        let times: Vec<f64> = self
+            .markers
+            .as_slice()
+            .iter()
+            .map(Duration::as_secs_f64)
+            .collect();
+        let wrap = self.cfg.application.wrap_markers;
+        let target = if forward {
+            // Seeks land on a frame, possibly just before the marker.
+            let next = times.iter().find(|&&m| m > pos + 0.01);
+            next.or(times.first().filter(|_| wrap))
+        } else {
+            let prev = times.iter().rev().find(|&&m| m < pos - MARKER_SLACK_SECS);
+            prev.or(times.last().filter(|_| wrap))
+        };
+        if let Some(&secs) = target {
+            self.seek_to_secs(secs);
+        }
+    }
+
+    fn export_markers(&mut self, audio: &Path) {
+        if self.markers.is_empty() {
+            return;
+        }
+        match write_sidecar(
+            audio,
+            self.markers.as_slice(),
+            self.cfg.application.read_only,
+        ) {
+            Ok(path) => info!("Wrote {} markers to {}", self.markers.len(), path.display()),
+            Err(e) => self.push_error(format!("Writing markers failed: {}", e)),
+        }
+    }
+
+    /// Ask where to save the markers as `HH:MM:SS` lines and write them.
+    fn export_marker_list(&mut self, audio: &Path) {
+        if self.markers.is_empty() {
+            return;
+        }
+        let stem = audio.file_stem().unwrap_or_default().to_string_lossy();
+        let mut dialog = FileDialog::new()
+            .set_file_name(format!("{}.timestamps.txt", stem))
+            .add_filter("Text", &["txt"]);
+        if let Some(dir) = audio.parent() {
+            dialog = dialog.set_directory(dir);
+        }
+        let Some(dest) = dialog.save_file() else {
+            return;
+        };
+        let text = timestamp_list(self.markers.as_slice());
+        match write_atomic(&dest, text.as_bytes()) {
+            Ok(()) => info!("Wrote {} markers to {}", self.markers.len(), dest.display()),
+            Err(e) => self.push_error(format!("Exporting markers failed: {}", e)),
+        }
+    }
+
+    /// Put the current position on the clipboard as `[MM:SS]`.
+    fn copy_timestamp(&mut self) {
+        let snap = self.player.snapshot();
+        if !snap.loaded {
+            return;
+        }
+        let stamp = format!("[{}]", format_span(snap.position_secs as u64));
+        info!("Copied timestamp {}", stamp);
+        self.egui_ctx.output_mut(|o| o.copied_text = stamp);
+    }
+
+    /// Pedals drive the archive dialog's buttons. Pressed states are still
+    /// tracked so releases after the dialog closes are handled normally.
+    /// Like the buttons, they do nothing while an archive is running.
+    fn apply_pedal_in_dialog(&mut self, code: u32, is_press: bool) {
+        let press = |pressed: bool| is_press && !pressed && self.archive_job.is_none();
+        if code == self.right_code {
+            if press(self.right_pressed) {
+                self.archive_from_dialog();
+            }
+            self.right_pressed = is_press;
+        } else if code == self.left_code {
+            if press(self.left_pressed) {
+                self.continue_from_archive_dialog();
+            }
+            self.left_pressed = is_press;
+        } else if code == self.middle_code {
+            self.dialog_middle_down = press(self.middle_pressed).then(Instant::now);
+            self.middle_pressed = is_press;
+        }
+        // A rewind held when the dialog opened stops here.
+        self.hold_last_tick = None;
+    }
+
+    fn tick_dialog_exit_hold(&mut self) {
+        let Some(down) = self.dialog_middle_down else {
+            return;
+        };
+        if !self.show_archive_dialog || !self.middle_pressed {
+            self.dialog_middle_down = None;
+        } else if down.elapsed() >= DIALOG_EXIT_HOLD {
+            self.dialog_middle_down = None;
+            self.exit_from_archive_dialog();
+        }
+    }
+
+    /// Left and right pressed within `chord_window_ms` of each other run the
+    /// chord action instead of their own. Returns true if the press was
+    /// consumed as the second half of a chord.
+    fn try_chord(&mut self, code: u32, at: Instant) -> bool {
+        let action = self.cfg.input.chord_left_right;
+        if action == ChordAction::Off {
+            return false;
+        }
+        let other_at = if code == self.left_code && !self.left_pressed && self.right_pressed {
+            self.right_press_at
+        } else if code == self.right_code && !self.right_pressed && self.left_pressed {
+            self.left_press_at
+        } else {
+            None
+        };
+        let Some(other_at) = other_at else {
+            return false;
+        };
+        let window = Duration::from_millis(self.cfg.input.chord_window_ms);
+        if at.saturating_duration_since(other_at) > window {
+            return false;
+        }
+
+        // Track the press so the release is handled normally, but undo what
+        // the first pedal did: hold-rewind arming, or its pre-roll seek and
+        // playback.
+        if code == self.left_code {
+            self.left_pressed = true;
+        } else {
+            self.right_pressed = true;
+        }
+        self.hold_last_tick = None;
+        self.player.pause();
+        if let Some((idx, was_playing)) = self.chord_undo.take() {
+            self.player.seek_to_index(idx);
+            if was_playing {
+                self.player.play_from_current();
+            }
+        }
+
+        info!("Pedal chord left+right: {:?}", action);
+        match action {
+            ChordAction::Off => {}
+            ChordAction::JumpToStart => self.player.seek_to_start(),
+            ChordAction::ArchiveDialog => self.open_archive_dialog(),
+        }
+        true
+    }
+
+    fn pedal_cue(&mut self, cue: Cue) {
+        let feedback = self.cfg.application.pedal_feedback;
+        if feedback.visual() {
+            self.flash = Some((cue, Instant::now()));
+        }
+        if feedback.audible() {
+            self.player.play_cue(cue.freq());
+        }
+    }
+
+    /// Button that briefly lights up when the matching pedal action fires.
+    fn cue_button(&self, text: &str, cue: Cue) -> egui::Button<'static> {
+        let button = egui::Button::new(text.to_owned());
+        match self.flash {
+            Some((c, at)) if c == cue && at.elapsed() < CUE_FLASH => button.fill(CUE_FLASH_COLOR),
+            _ => button,
+        }
+    }
+
+    fn tick_hold_rewind(&mut self) {
+        let Some(last) = self.hold_last_tick else {
+            return;
+        };
+        let interval = Duration::from_millis(self.cfg.application.hold_rewind_interval_ms);
+        if last.elapsed() >= interval {
+            // Rewind while playing continues
+            let back = -(self.cfg.application.rewind_seconds as i64);
+            self.player.seek_seconds(back);
+            self.hold_last_tick = Some(Instant::now());
+            self.pedal_cue(Cue::Rewind);
+        }
+    }
+
+    fn open_archive_dialog(&mut self) {
+        if self.cfg.application.read_only {
+            // Review mode: the archive action only pauses.
+            info!("Read-only mode: archiving disabled");
+            self.player.pause();
+            return;
+        }
+        self.archive_resume = Some((self.player.current_index_interleaved(), self.player.playing));
+        if self.cfg.application.pause_on_archive_dialog {
+            self.player.pause();
+        }
+        self.show_archive_dialog = true;
+    }
+
+    /// Close the archive dialog without archiving.
+    fn continue_from_archive_dialog(&mut self) {
+        self.show_archive_dialog = false;
+        self.archive_error = None;
+        let resume = self.archive_resume.take();
+        if self.cfg.application.resume_on_continue {
+            if let Some((idx, was_playing)) = resume {
+                self.player.resume_from(idx, was_playing);
+            }
+        }
+    }
+
+    /// Open the file before or after the current one in its folder.
+    fn open_sibling(&mut self, forward: bool) {
+        let Some(current) = self.player.file_path.clone() else {
+            return;
+        };
+        let wrap = self.cfg.application.wrap_file_navigation;
+        match sibling_file(&current, AUDIO_EXTENSIONS, forward, wrap) {
+            Some(path) => self.open_path(&path),
+            None => info!(
+                "No {} file in {}",
+                if forward { "next" } else { "previous" },
+                current
+                    .parent()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default()
+            ),
+        }
+    }
+
+    fn toggle_loop_last(&mut self) {
+        if self.player.loop_region.is_some() {
+            self.player.clear_loop();
+        } else {
+            self.player
+                .loop_last(self.cfg.application.loop_last_seconds);
+        }
+    }
+
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if self.show_archive_dialog {
+            // Escape acts as "Continue"; consume it so nothing else sees it.
+            if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+                self.continue_from_archive_dialog();
+            }
+            return;
+        }
+        if let Some(shortcut) = self.archive_shortcut {
+            if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) && self.player.file_path.is_some() {
+                self.open_archive_dialog();
+            }
+        }
+        // Bare keys belong to a text field (Settings, dialogs) while it
+        // has focus.
+        let typing = ctx.wants_keyboard_input();
+        if !typing && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Home)) {
+            self.player.jump_to_edge(false);
+        }
+        if !typing && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::End)) {
+            self.player.jump_to_edge(true);
+        }
+        if !typing && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::K)) {
+            self.insert_marker();
+        }
+        if !typing && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::PageDown)) {
+            self.jump_to_marker(true);
+        }
+        if !typing && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::PageUp)) {
+            self.jump_to_marker(false);
+        }
+        if !typing && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::M)) {
+            self.player.toggle_mute();
+        }
+        if !typing && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::L)) {
+            self.toggle_loop_last();
+        }
+    }
+
+    fn ui_top_bar(&mut self, ui: &mut egui::Ui) {
+        // Buttons: Open, Play/Pause, Rewind, Forward, Speed dropdown, Archive
+        if ui
+            .add_enabled(self.decode_job.is_none(), egui::Button::new("Open"))
+            .clicked()
+        {
+            if self.cfg.paths.prompt_if_unreachable && !self.cfg.default_open_dir_reachable() {
+                warn!(
+                    "Open dir unreachable: {}",
+                    self.cfg.paths.default_open_dir.display()
+                );
+                self.show_open_dir_prompt = true;
+                self.open_dir_still_unreachable = false;
+            } else {
+                self.pick_and_open(self.cfg.resolve_default_open_dir());
+            }
+        }
+
+        let can_step = self.player.file_path.is_some() && self.decode_job.is_none();
+        if ui
+            .add_enabled(can_step, egui::Button::new("Prev"))
+            .on_hover_text("Open the previous file in this folder")
+            .clicked()
+        {
+            self.open_sibling(false);
+        }
+        if ui
+            .add_enabled(can_step, egui::Button::new("Next"))
+            .on_hover_text("Open the next file in this folder")
+            .clicked()
+        {
+            self.open_sibling(true);
+        }
+
+        let can_control = self.player.audio.is_some();
+
+        if ui
+            .add_enabled(
+                can_control,
+                self.cue_button(
+                    if self.player.playing { "Pause" } else { "Play" },
+                    Cue::Play,
+                ),
+            )
+            .clicked()
+        {
+            if self.player.playing {
+                self.player.pause();
+            } else {
+                self.player.play_from_current();
+            }
+        }
+
+        if ui
+            .add_enabled(can_control, self.cue_button("Rewind", Cue::Rewind))
+            .clicked()
+        {
+            let back = -(self.cfg.application.rewind_seconds as i64);
+            self.player.seek_seconds(back);
+        }
+
+        if ui
+            .add_enabled(can_control, egui::Button::new("Forward"))
+            .clicked()
+        {
+            self.player
+                .seek_seconds(self.cfg.application.forward_seconds as i64);
+        }
+
+        for step in self.quick_seeks.clone() {
+            if ui
+                .add_enabled(can_control, egui::Button::new(format!("{:+}s", step)))
+                .clicked()
+            {
+                self.player.seek_seconds(step);
+            }
+        }
+
+        if ui
+            .add_enabled(can_control, egui::Button::new("|<"))
+            .on_hover_text("Jump to the start (Home)")
+            .clicked()
+        {
+            self.player.jump_to_edge(false);
+        }
+        if ui
+            .add_enabled(can_control, egui::Button::new(">|"))
+            .on_hover_text("Jump to the end (End)")
+            .clicked()
+        {
+            self.player.jump_to_edge(true);
+        }
+
+        if ui
+            .add_enabled(
+                can_control && self.player.playing,
+                egui::Button::new("Commit position"),
+            )
+            .on_hover_text("Pin the live position for the next seeks, without pausing")
+            .clicked()
+        {
+            self.player.commit_position();
+        }
+
+        ui.separator();
+
+        if ui
+            .add_enabled(can_control, egui::Button::new("Add marker"))
+            .on_hover_text("Mark the current position (K)")
+            .clicked()
+        {
+            self.insert_marker();
+        }
+        let has_markers = can_control && !self.markers.is_empty();
+        if ui
+            .add_enabled(has_markers, egui::Button::new("Prev marker"))
+            .on_hover_text("Jump to the marker before the current position (PageUp)")
+            .clicked()
+        {
+            self.jump_to_marker(false);
+        }
+        if ui
+            .add_enabled(has_markers, egui::Button::new("Next marker"))
+            .on_hover_text("Jump to the next marker (PageDown)")
+            .clicked()
+        {
+            self.jump_to_marker(true);
+        }
+        if let Some(path) = self.player.file_path.clone() {
+            if ui
+                .add_enabled(
+                    !self.markers.is_empty(),
+                    egui::Button::new(format!("Export markers ({})", self.markers.len())),
+                )
+                .on_hover_text(if self.cfg.application.read_only {
+                    "Write the markers to a .markers.txt in the data folder"
+                } else {
+                    "Write the markers to a .markers.txt next to the file"
+                })
+                .clicked()
+            {
+                self.export_markers(&path);
+            }
+            if ui
+                .add_enabled(!self.markers.is_empty(), egui::Button::new("Export list…"))
+                .on_hover_text("Save the markers as HH:MM:SS lines for another tool")
+                .on_disabled_hover_text("No markers to export yet")
+                .clicked()
+            {
+                self.export_marker_list(&path);
+            }
+        }
+        if ui
+            .add_enabled(!self.markers.is_empty(), egui::Button::new("Clear markers"))
+            .on_hover_text("Remove all markers from this file")
+            .clicked()
+        {
+            self.markers.clear();
+        }
+
+        ui.separator();
+
+        let (range, step) = self.cfg.application.speed_range();
+        let mut speed = self.player.speed;
+        if ui
+            .add(
+                egui::Slider::new(&mut speed, range.clone())
+                    .step_by(step as f64)
+                    .fixed_decimals(2)
+                    .suffix("x"),
+            )
+            .changed()
+        {
+            self.player.set_speed(speed);
+        }
+        egui::ComboBox::from_label("Speed")
+            .selected_text(format!("{:.2}x", self.player.speed))
+            .show_ui(ui, |ui| {
+                for s in SPEED_PRESETS.into_iter().filter(|s| range.contains(s)) {
+                    if ui
+                        .selectable_label(
+                            (self.player.speed - s).abs() < 1e-3,
+                            format!("{:.2}x", s),
                         )
                         .clicked()
                     {
@@ -296,25 +1437,204 @@ impl App {
                 }
             });
 
+        let mut volume = self.player.volume;
+        let slider = ui
+            .add(
+                egui::Slider::new(&mut volume, 0.0..=2.0)
+                    .text("Volume")
+                    .fixed_decimals(2),
+            )
+            .on_hover_text("Playback gain (1.00 = original level)");
+        if slider.changed() {
+            self.player.set_volume(volume);
+        }
+        if self.player.audio.is_some() && (self.player.norm_gain - 1.0).abs() > 1e-3 {
+            ui.weak(format!(
+                "norm {:+.1} dB",
+                20.0 * self.player.norm_gain.log10()
+            ))
+            .on_hover_text("Gain from application.normalize for this file");
+        }
+        if slider.drag_released() || (slider.changed() && !slider.dragged()) {
+            self.save_volume();
+        }
+
+        let mut voice_filter = self.player.voice_filter;
+        if ui
+            .checkbox(&mut voice_filter, "High-pass")
+            .on_hover_text(format!(
+                "Cut rumble below {} Hz{}",
+                self.player.highpass_hz,
+                if self.player.presence_boost {
+                    " and lift speech presence"
+                } else {
+                    ""
+                }
+            ))
+            .changed()
+        {
+            self.player.set_voice_filter(voice_filter);
+        }
+
+        if ui
+            .selectable_label(self.player.skip_silence, "Skip silence")
+            .on_hover_text(format!(
+                "Jump over quiet stretches longer than {} ms",
+                self.cfg.application.silence_min_ms
+            ))
+            .clicked()
+        {
+            self.player.skip_silence = !self.player.skip_silence;
+        }
+
+        if ui
+            .selectable_label(self.player.muted, "Mute")
+            .on_hover_text("Toggle mute (M)")
+            .clicked()
+        {
+            self.player.toggle_mute();
+        }
+        if self.player.muted {
+            ui.colored_label(Color32::YELLOW, "MUTED");
+        }
+
+        let looping = self.player.loop_region.is_some();
+        let snap_rate = self
+            .player
+            .audio
+            .as_ref()
+            .map(|a| (a.sample_rate as u64, a.channels as usize));
+        if ui
+            .add_enabled(can_control, egui::SelectableLabel::new(looping, "Loop"))
+            .on_hover_text(format!(
+                "Loop the last {} s (L); any seek clears it",
+                self.cfg.application.loop_last_seconds
+            ))
+            .clicked()
+        {
+            self.toggle_loop_last();
+        }
+
+        let loop_point_hover = |point: Option<usize>, name: &str| match (point, snap_rate) {
+            (Some(idx), Some((sr, ch))) => format!(
+                "Loop point {} at {}; click to move it here",
+                name,
+                format_span((idx / ch) as u64 / sr)
+            ),
+            _ => format!("Set loop point {} at the current position", name),
+        };
+        if ui
+            .add_enabled(
+                can_control,
+                egui::SelectableLabel::new(self.player.loop_a.is_some(), "Set A"),
+            )
+            .on_hover_text(loop_point_hover(self.player.loop_a, "A"))
+            .clicked()
+        {
+            self.player.set_loop_a();
+        }
+        if ui
+            .add_enabled(
+                can_control,
+                egui::SelectableLabel::new(self.player.loop_b.is_some(), "Set B"),
+            )
+            .on_hover_text(loop_point_hover(self.player.loop_b, "B"))
+            .clicked()
+        {
+            self.player.set_loop_b();
+        }
+        let has_loop = self.player.loop_region.is_some()
+            || self.player.loop_a.is_some()
+            || self.player.loop_b.is_some();
+        if ui
+            .add_enabled(has_loop, egui::Button::new("Clear Loop"))
+            .clicked()
+        {
+            self.player.clear_loop();
+        }
+        let mut repeats = self.player.loop_repeats;
+        if ui
+            .add(
+                egui::DragValue::new(&mut repeats)
+                    .clamp_range(0..=99)
+                    .prefix("Repeats: "),
+            )
+            .on_hover_text(
+                "Times the A/B loop goes back to A before playing on; 0 loops until cleared",
+            )
+            .changed()
+        {
+            self.player.set_loop_repeats(repeats);
+        }
+
         ui.separator();
 
+        let read_only = self.cfg.application.read_only;
         if ui
-            .add_enabled(can_control, egui::Button::new("Archive"))
+            .add_enabled(
+                self.player.file_path.is_some() && !read_only,
+                self.cue_button("Archive", Cue::Archive),
+            )
+            .on_disabled_hover_text(if read_only {
+                "Disabled in read-only mode"
+            } else {
+                "No file loaded"
+            })
             .clicked()
         {
-            self.player.pause();
-            self.show_archive_dialog = true;
+            self.open_archive_dialog();
+        }
+        if let Some((src, dest)) = &self.last_archive {
+            let hover = format!("Move {} back to {}", dest.display(), src.display());
+            if ui
+                .add_enabled(
+                    self.archive_job.is_none() && self.restore_job.is_none() && !read_only,
+                    egui::Button::new("Undo archive"),
+                )
+                .on_hover_text(hover)
+                .clicked()
+            {
+                self.undo_archive();
+            }
+        }
+        if read_only {
+            ui.colored_label(Color32::LIGHT_BLUE, "READ-ONLY");
         }
 
         ui.separator();
 
+        ui.menu_button("Settings", |ui| {
+            if ui.button("Edit settings…").clicked() {
+                self.settings = match self.settings {
+                    Some(_) => None,
+                    None => Some(SettingsForm::new(&self.cfg)),
+                };
+                ui.close_menu();
+            }
+            if ui.button("Pedal diagnostics…").clicked() {
+                self.show_pedal_diag = !self.show_pedal_diag;
+                ui.close_menu();
+            }
+            if ui.button("Reset config to defaults…").clicked() {
+                self.show_reset_config_confirm = true;
+                ui.close_menu();
+            }
+        });
+
+        ui.separator();
+
         // Status and Errors
         let pedal_text = match &self.pedal_status {
             PedalStatus::Scanning => "Pedal: Scanning".to_owned(),
-            PedalStatus::Connected { name, path } => {
+            PedalStatus::Connected { name, path, .. } => {
                 format!("Pedal: Connected ({}, {})", name, path.display())
             }
-            PedalStatus::NotFound => "Pedal: Not found".to_owned(),
+            PedalStatus::NotFound { retry_in } => {
+                format!(
+                    "Pedal: Not found (rescan every {}s)",
+                    retry_in.as_secs_f32().round()
+                )
+            }
             PedalStatus::Error(e) => format!("Pedal: Error ({})", e),
         };
         ui.label(pedal_text);
@@ -323,8 +1643,15 @@ impl App {
 
         // Show dismissible errors (non-fatal) in red
         let mut to_remove: Vec<u64> = Vec::new();
+        let mut retry = None;
         for e in &self.errors {
             ui.colored_label(Color32::RED, format!("Error: {}", e.msg));
+            if let Some((_, path)) = self.mono_retry.as_ref().filter(|(id, _)| *id == e.id) {
+                if ui.button("Open as mono (downmix)").clicked() {
+                    to_remove.push(e.id);
+                    retry = Some(path.clone());
+                }
+            }
         }
         if !self.errors.is_empty() {
             if ui.button("Dismiss errors").clicked() {
@@ -332,13 +1659,211 @@ impl App {
             }
         }
         if !to_remove.is_empty() {
-            self.errors.retain(|e| !to_remove.contains(&e.id));
+            self.remove_errors(&to_remove);
+        }
+        if let Some(path) = retry {
+            self.open_path_with(&path, true);
         }
     }
 
-    fn ui_central(&mut self, ui: &mut egui::Ui) {
-        let name = self
+    fn pick_and_open(&mut self, start_dir: PathBuf) {
+        if let Some(path) = FileDialog::new()
+            .set_directory(start_dir)
+            .add_filter("Audio", AUDIO_EXTENSIONS)
+            .pick_file()
+        {
+            self.open_path(&path);
+        }
+    }
+
+    fn open_path(&mut self, path: &Path) {
+        self.open_path_with(path, false);
+    }
+
+    /// Start decoding `path` on a worker thread; `poll_decode` loads it
+    /// once done. Playback pauses meanwhile.
+    fn open_path_with(&mut self, path: &Path, force_mono: bool) {
+        if self.decode_job.is_some() {
+            return;
+        }
+        let progress = Arc::new(DecodeProgress::default());
+        let opts = self.player.decode_options(&DecodeOptions {
+            force_mono,
+            progress: Some(progress.clone()),
+            ..DecodeOptions::from_config(&self.cfg.application)
+        });
+        self.mono_retry = None;
+        self.player.pause();
+
+        let (tx, rx) = mpsc::channel();
+        let worker_path = path.to_path_buf();
+        let ctx = self.egui_ctx.clone();
+        let spawned = thread::Builder::new().name("decode".into()).spawn(move || {
+            let _ = tx.send(decode_to_f32_interleaved(&worker_path, &opts));
+            ctx.request_repaint();
+        });
+        match spawned {
+            Ok(_) => {
+                self.decode_job = Some(DecodeJob {
+                    path: path.to_path_buf(),
+                    progress,
+                    rx,
+                });
+            }
+            Err(e) => self.push_error(format!("Open failed: {}", e)),
+        }
+    }
+
+    fn start_waveform(&mut self) {
+        self.waveform = self
             .player
+            .audio
+            .as_ref()
+            .and_then(|a| Waveform::compute(&a.samples));
+    }
+
+    fn poll_decode(&mut self) {
+        let Some(job) = &self.decode_job else {
+            return;
+        };
+        let res = match job.rx.try_recv() {
+            Ok(res) => res,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Err(anyhow::anyhow!("decoder stopped unexpectedly"))
+            }
+        };
+        let path = job.path.clone();
+        self.decode_job = None;
+        match res {
+            Ok(decoded) => {
+                self.player.load_decoded(&path, decoded);
+                self.markers.clear();
+                self.start_waveform();
+                info!("Opened file: {}", path.display());
+                self.offer_resume(&path);
+            }
+            Err(e) => {
+                let id = self.push_error_id(format!("Open failed: {}", e));
+                if e.downcast_ref::<UnsupportedChannels>().is_some() {
+                    self.mono_retry = Some((id, path));
+                }
+            }
+        }
+    }
+
+    fn ui_open_dir_prompt(&mut self, ctx: &egui::Context) {
+        if !self.show_open_dir_prompt {
+            return;
+        }
+        egui::Window::new("Folder unavailable")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("The configured folder is not reachable:");
+                ui.monospace(self.cfg.paths.default_open_dir.display().to_string());
+                ui.label("If it is a network share, it may not be mounted yet.");
+                if self.open_dir_still_unreachable {
+                    ui.colored_label(Color32::RED, "Still unreachable.");
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Retry").clicked() {
+                        if self.cfg.default_open_dir_reachable() {
+                            self.show_open_dir_prompt = false;
+                            self.pick_and_open(self.cfg.paths.default_open_dir.clone());
+                        } else {
+                            self.open_dir_still_unreachable = true;
+                        }
+                    }
+                    if ui.button("Browse elsewhere").clicked() {
+                        self.show_open_dir_prompt = false;
+                        self.pick_and_open(self.cfg.resolve_default_open_dir());
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.show_open_dir_prompt = false;
+                    }
+                });
+            });
+    }
+
+    /// Collapsed-by-default summary of the loaded file's format.
+    fn ui_details(&self, ui: &mut egui::Ui) {
+        let Some(audio) = &self.player.audio else {
+            return;
+        };
+        let src = audio.source;
+        egui::CollapsingHeader::new("Details")
+            .default_open(false)
+            .show(ui, |ui| {
+                egui::Grid::new("details_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Codec");
+                        ui.label(audio.codec);
+                        ui.end_row();
+                        ui.label("Channels");
+                        ui.label(match src.channels {
+                            1 => "1 (mono)".to_owned(),
+                            2 => "2 (stereo)".to_owned(),
+                            n => n.to_string(),
+                        });
+                        ui.end_row();
+                        ui.label("Sample rate");
+                        ui.label(format!("{} Hz", src.sample_rate));
+                        ui.end_row();
+                        if let Some(bits) = src.bits_per_sample {
+                            ui.label("Bit depth");
+                            ui.label(format!("{} bit", bits));
+                            ui.end_row();
+                        }
+                        if let Some(kbps) = src.bitrate_kbps {
+                            ui.label("Bitrate");
+                            ui.label(format!("{} kbps (average)", kbps));
+                            ui.end_row();
+                        }
+                        ui.label("Duration");
+                        ui.label(format_span(self.player.snapshot().duration_secs as u64));
+                        ui.end_row();
+                        if src.channels != audio.channels as usize
+                            || src.sample_rate != audio.sample_rate
+                        {
+                            ui.label("Playing as");
+                            ui.label(format!("{} ch, {} Hz", audio.channels, audio.sample_rate));
+                            ui.end_row();
+                        }
+                        if audio.skipped_packets > 0 {
+                            ui.label("Skipped packets");
+                            ui.label(audio.skipped_packets.to_string());
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn ui_central(&mut self, ui: &mut egui::Ui) {
+        if let Some(job) = &self.decode_job {
+            let name = job
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            ui.heading(name);
+            ui.label("Decoding…");
+            let bar = match job.progress.fraction() {
+                Some(frac) => egui::ProgressBar::new(frac).show_percentage(),
+                None => egui::ProgressBar::new(0.0).animate(true),
+            };
+            ui.add(bar);
+            return;
+        }
+        let snap = self.player.snapshot();
+        if ui.available_width() < self.cfg.application.compact_width {
+            self.ui_compact_badge(ui, &snap);
+            return;
+        }
+        let name = snap
             .file_path
             .as_ref()
             .map(|p| {
@@ -346,28 +1871,165 @@ impl App {
                     .and_then(|s| s.to_str())
                     .unwrap_or("(invalid)")
             })
-            .unwrap_or("No file selected");
+            .unwrap_or(if snap.loaded {
+                "(stdin)"
+            } else {
+                "No file selected"
+            });
         ui.heading(name);
+        self.ui_details(ui);
 
-        // Time/progress
-        let (cur, total) = self.player.current_time_secs();
-        ui.label(format_clock(cur, total));
-
-        // Progress bar (read-only)
-        let frac = if let Some(audio) = &self.player.audio {
-            let idx = self.player.current_index_interleaved();
-            if audio.total_samples == 0 {
-                0.0
+        // Time/progress; flag non-1.0x speed so content time isn't read as real time
+        ui.horizontal(|ui| {
+            ui.label(if self.cfg.application.clock_millis {
+                format_clock_ms(snap.position_secs, snap.duration_secs)
             } else {
-                (idx as f32) / (audio.total_samples as f32)
+                format_clock(
+                    snap.position_secs.floor() as u64,
+                    snap.duration_secs.floor() as u64,
+                )
+            });
+            if self.cfg.application.show_remaining && snap.loaded {
+                ui.label(format_remaining(
+                    snap.remaining_wall_secs().ceil() as u64,
+                    snap.duration_secs.floor() as u64,
+                ))
+                .on_hover_text("Time left at the current speed");
+            }
+            if (snap.speed - 1.0).abs() > f32::EPSILON {
+                ui.label(
+                    egui::RichText::new(format!(" {:.2}x ", snap.speed))
+                        .strong()
+                        .color(Color32::BLACK)
+                        .background_color(SPEED_BADGE_COLOR),
+                );
+            }
+        });
+
+        // Waveform overview, once computed; seeks like the progress bar.
+        if snap.loaded {
+            let frac = snap.fraction();
+            if let Some(wave) = self
+                .waveform
+                .as_mut()
+                .and_then(|w| w.show(ui, WAVEFORM_HEIGHT, frac))
+            {
+                self.scrub_progress_bar(&wave, &snap);
+            }
+        }
+
+        // Progress bar; click to jump, drag to scrub. Hovering shows the
+        // time under the cursor.
+        let bar = ui.add(
+            egui::ProgressBar::new(snap.fraction())
+                .desired_height(self.cfg.application.progress_bar_height.max(4.0))
+                .show_percentage(),
+        );
+        let bar = ui.interact(bar.rect, bar.id, egui::Sense::click_and_drag());
+        if snap.loaded && snap.duration_secs > 0.0 {
+            let rect = bar.rect;
+            let stroke = egui::Stroke::new(2.0, MARKER_COLOR);
+            for m in self.markers.as_slice() {
+                let frac = (m.as_secs_f64() / snap.duration_secs).clamp(0.0, 1.0) as f32;
+                let x = rect.left() + rect.width() * frac;
+                ui.painter().line_segment(
+                    [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                    stroke,
+                );
             }
+        }
+        if snap.loaded {
+            self.scrub_progress_bar(&bar, &snap);
+        }
+        if snap.loaded {
+            if let Some(pos) = bar.hover_pos() {
+                let rect = bar.rect;
+                let frac = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0) as f64;
+                let at = snap.duration_secs * frac;
+                bar.on_hover_text_at_pointer(format!(
+                    "{} (-{})",
+                    format_span(at.floor() as u64),
+                    format_span((snap.duration_secs - at).floor() as u64)
+                ));
+            }
+        }
+
+        if self.cfg.application.debug_readout {
+            self.ui_debug_readout(ui);
+        }
+    }
+
+    /// Seek from clicks/drags on the progress bar. Playback pauses while
+    /// dragging so the live position doesn't fight the playhead, and resumes
+    /// from the drop point.
+    fn scrub_progress_bar(&mut self, bar: &egui::Response, snap: &PlaybackSnapshot) {
+        let idx = bar.interact_pointer_pos().map(|pos| {
+            let frac = ((pos.x - bar.rect.left()) / bar.rect.width()).clamp(0.0, 1.0) as f64;
+            (snap.total_samples as f64 * frac) as usize
+        });
+        if bar.drag_started() {
+            self.scrub_resume = Some(self.player.playing);
+            self.player.pause();
+        }
+        if bar.dragged() || bar.drag_released() || bar.clicked() {
+            if let Some(idx) = idx {
+                self.player.seek_to_index(idx);
+            }
+        }
+        if bar.drag_released() && self.scrub_resume.take() == Some(true) {
+            self.player.play_from_current();
+        }
+    }
+
+    /// Narrow window: one big time readout, legible from across the desk.
+    fn ui_compact_badge(&self, ui: &mut egui::Ui, snap: &PlaybackSnapshot) {
+        let text = if !snap.loaded {
+            "--:--".to_owned()
+        } else {
+            match self.cfg.application.compact_badge {
+                CompactBadge::Remaining => format_remaining(
+                    snap.remaining_secs().ceil() as u64,
+                    snap.duration_secs.floor() as u64,
+                ),
+                CompactBadge::Elapsed => format_span(snap.position_secs.floor() as u64),
+            }
+        };
+        let color = if (snap.speed - 1.0).abs() > f32::EPSILON {
+            SPEED_BADGE_COLOR
         } else {
-            0.0
+            ui.visuals().strong_text_color()
+        };
+        ui.vertical_centered(|ui| {
+            ui.label(
+                egui::RichText::new(text)
+                    .monospace()
+                    .size(COMPACT_BADGE_SIZE)
+                    .color(color),
+            );
+        });
+    }
+
+    fn ui_debug_readout(&self, ui: &mut egui::Ui) {
+        let Some(audio) = &self.player.audio else {
+            ui.monospace("debug: no audio loaded");
+            return;
         };
-        ui.add(egui::ProgressBar::new(frac).show_percentage());
+        let ch = audio.channels as usize;
+        let live = self.player.current_index_interleaved();
+        ui.monospace(format!(
+            "content_index={} live={} (frame {}) total_samples={} (frames {}) ch={} sr={} playing={}",
+            self.player.content_index,
+            live,
+            live / ch,
+            audio.total_samples,
+            audio.total_samples / ch,
+            audio.channels,
+            audio.sample_rate,
+            self.player.playing,
+        ));
     }
 
-    fn ui_archive_dialog(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+    fn ui_archive_dialog(&mut self, ctx: &egui::Context) {
         if !self.show_archive_dialog {
             return;
         }
@@ -379,86 +2041,202 @@ impl App {
                 if let Some(err) = &self.archive_error {
                     ui.colored_label(Color32::RED, err);
                 }
+                if self.archive_job.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(if self.archive_pending_exit {
+                            "Archiving, will exit when done…"
+                        } else {
+                            "Archiving…"
+                        });
+                    });
+                }
 
-                ui.horizontal(|ui| {
-                    if ui.button("Archive").clicked() {
-                        match self.do_archive(false) {
-                            Ok(()) => {
-                                self.show_archive_dialog = false;
-                            }
-                            Err(e) => {
-                                self.archive_error = Some(format!("Archive failed: {}", e));
-                            }
+                ui.add_enabled_ui(self.archive_job.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("Archive").clicked() {
+                            self.archive_from_dialog();
                         }
-                    }
-                    if ui.button("Continue").clicked() {
-                        self.show_archive_dialog = false; // leave playback paused
-                        self.archive_error = None;
-                    }
-                    if ui.button("Exit").clicked() {
-                        self.archive_pending_exit = true;
-                        match self.do_archive(true) {
-                            Ok(()) => {
-                                self.request_close = true;
-                            }
-                            Err(e) => {
-                                self.archive_error = Some(format!("Archive failed: {}", e));
-                                self.archive_pending_exit = false;
-                            }
+                        if ui.button("Continue").clicked() {
+                            // Leaves playback paused unless resume_on_continue
+                            self.continue_from_archive_dialog();
                         }
-                    }
+                        if ui.button("Exit").clicked() {
+                            self.exit_from_archive_dialog();
+                        }
+                    })
                 });
             });
-        if self.request_close {
-            frame.close();
+    }
+
+    fn archive_from_dialog(&mut self) {
+        if let Err(e) = self.do_archive(false) {
+            self.archive_error = Some(format!("Archive failed: {}", e));
+        }
+    }
+
+    /// Archive the loaded file and quit once that finished; with nothing
+    /// loaded, just quit.
+    fn exit_from_archive_dialog(&mut self) {
+        if self.player.file_path.is_none() {
+            self.request_close = true;
+            return;
+        }
+        if let Err(e) = self.do_archive(true) {
+            self.archive_error = Some(format!("Archive failed: {}", e));
         }
     }
 
-    fn do_archive(&mut self, _exit_after: bool) -> anyhow::Result<()> {
-        // Move/copy file, then unload
+    /// Start archiving the loaded file in the background. The outcome is
+    /// picked up by `poll_archive`.
+    fn do_archive(&mut self, exit_after: bool) -> anyhow::Result<()> {
+        if self.archive_job.is_some() {
+            return Err(anyhow::anyhow!("An archive is already in progress"));
+        }
         let Some(src) = self.player.file_path.clone() else {
             return Err(anyhow::anyhow!("No file selected"));
         };
+        if self.cfg.application.read_only {
+            return Err(anyhow::anyhow!("Archiving is disabled in read-only mode"));
+        }
 
-        // ./archive/YYYY/MM
-        let now = Local::now();
-        let dest_dir = PathBuf::from("./archive")
-            .join(format!("{:04}", now.year()))
-            .join(format!("{:02}", now.month()));
-        std::fs::create_dir_all(&dest_dir)?;
-
-        // Append suffix _YYYYMMDD_HHMMSS before extension
-        let stem = src.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
-        let ext = src.extension().and_then(|s| s.to_str()).unwrap_or("");
-        let ts = now.format("%Y%m%d_%H%M%S").to_string();
-        let filename = if ext.is_empty() {
-            format!("{}_{}", stem, ts)
-        } else {
-            format!("{}_{}.{}", stem, ts, ext)
+        // Playback may still be running if the dialog didn't pause it.
+        self.player.pause();
+        self.was_playing = false;
+        // While the file still exists, so its canonical path resolves.
+        self.archive_position_key = self.positions.is_some().then(|| positions::key(&src));
+        self.archive_error = None;
+        self.archive_pending_exit = exit_after;
+        self.archive_job = Some(archive_in_background(
+            src,
+            ArchiveOptions::from_config(&self.cfg),
+        ));
+        Ok(())
+    }
+
+    /// Start moving the last archived file back in the background;
+    /// `poll_restore` opens it again once it is there.
+    fn undo_archive(&mut self) {
+        if self.archive_job.is_some() || self.restore_job.is_some() {
+            return;
+        }
+        let Some((src, dest)) = self.last_archive.clone() else {
+            return;
+        };
+        self.restore_job = Some(restore_in_background(
+            dest,
+            src,
+            self.cfg.application.archive_ledger,
+        ));
+    }
+
+    fn poll_restore(&mut self) {
+        let Some(rx) = &self.restore_job else {
+            return;
+        };
+        let res = match rx.try_recv() {
+            Ok(res) => res,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.restore_job = None;
+                self.push_error("Undo archive failed: restore worker stopped unexpectedly");
+                return;
+            }
         };
-        let dest = dest_dir.join(filename);
-
-        // Try rename first
-        match std::fs::rename(&src, &dest) {
-            Ok(()) => {
-                info!("Archived (rename): {} -> {}", src.display(), dest.display());
-            }
-            Err(_) => {
-                // Copy then delete
-                std::fs::copy(&src, &dest)?;
-                std::fs::remove_file(&src)?;
-                info!(
-                    "Archived (copy+delete): {} -> {}",
-                    src.display(),
-                    dest.display()
+        self.restore_job = None;
+        if self.restore_pending_exit {
+            self.request_close = true;
+        }
+        match res.restored {
+            Ok(hash) => {
+                self.last_archive = None;
+                let detail = format!(
+                    "from={} to={}",
+                    res.archived.display(),
+                    res.original.display()
                 );
+                self.log_event("unarchived", Some(&detail));
+                if let Some(hash) = hash {
+                    record_restore(res.archived, res.original.clone(), res.restored_at, hash);
+                }
+                if !self.restore_pending_exit {
+                    self.open_path(&res.original);
+                }
             }
+            Err(e) => self.push_error(format!("Undo archive failed: {}", e)),
         }
+    }
 
-        // Return to "No file selected"
-        self.player.unload();
-
-        Ok(())
+    /// Finish a background archive: unload and close the dialog (and the
+    /// window, for Exit) on success; on failure cancel any pending exit and
+    /// show the error in the dialog again.
+    fn poll_archive(&mut self) {
+        let Some(rx) = &self.archive_job else {
+            return;
+        };
+        let res = match rx.try_recv() {
+            Ok(res) => res,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => ArchiveResult {
+                src: self.player.file_path.clone().unwrap_or_default(),
+                archived_at: chrono::Local::now(),
+                archived: Err(anyhow::anyhow!("archive worker stopped unexpectedly")),
+            },
+        };
+        self.archive_job = None;
+        let position_key = self.archive_position_key.take();
+        match res.archived {
+            Ok(Archived { dest, source_hash }) => {
+                // Return to "No file selected", unless another file was
+                // opened meanwhile.
+                let was_loaded = self.player.file_path.as_deref() == Some(res.src.as_path());
+                // Before the unload, and naming the archived file even if
+                // another one was opened meanwhile.
+                if let Some(log) = &mut self.event_log {
+                    let mut snap = self.player.snapshot();
+                    snap.file_path = Some(res.src.clone());
+                    log.record("archived", &snap, Some(&format!("dest={}", dest.display())));
+                }
+                if was_loaded {
+                    self.player.unload();
+                    self.waveform = None;
+                }
+                let advance_to = (was_loaded
+                    && self.cfg.application.advance_after_archive
+                    && !self.archive_pending_exit)
+                    .then(|| {
+                        sibling_file(
+                            &res.src,
+                            AUDIO_EXTENSIONS,
+                            true,
+                            self.cfg.application.wrap_file_navigation,
+                        )
+                    })
+                    .flatten();
+                if was_loaded {
+                    self.export_markers(&dest);
+                    self.markers.clear();
+                }
+                self.last_archive = Some((res.src.clone(), dest.clone()));
+                if let (Some(store), Some(key)) = (&mut self.positions, position_key) {
+                    store.remove(&key);
+                }
+                if let Some(hash) = source_hash.filter(|_| self.cfg.application.archive_ledger) {
+                    record_archive(res.src, dest, res.archived_at, hash);
+                }
+                self.show_archive_dialog = false;
+                if self.archive_pending_exit {
+                    self.request_close = true;
+                } else if let Some(next) = advance_to {
+                    self.open_path(&next);
+                }
+            }
+            Err(e) => {
+                self.archive_error = Some(format!("Archive failed: {}", e));
+                self.archive_pending_exit = false;
+                self.show_archive_dialog = true;
+            }
+        }
     }
 }
 
@@ -466,12 +2244,38 @@ impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         // Drain pedal messages
         self.drain_pedal_msgs();
+        self.drain_control_cmds();
+        self.poll_config_reload();
+        self.expire_errors();
+        self.settle_bounced_pedals(Instant::now());
+        self.poll_keyboard_pedals(ctx);
+        self.poll_archive();
+        self.poll_restore();
+        self.poll_decode();
 
         // Handle repeated rewind if left is pressed
         self.tick_hold_rewind();
+        self.tick_dialog_exit_hold();
 
         // Clamp at end
-        self.player.clamp_at_end_if_needed();
+        if self.player.clamp_at_end_if_needed()
+            && self.cfg.application.end_beep
+            && !self.player.muted
+        {
+            self.player.play_tone(END_BEEP_FREQ, END_BEEP_LEN);
+        }
+        self.player.finish_counted_loop();
+        self.player.skip_silence_if_needed(
+            self.cfg.application.silence_threshold_db,
+            self.cfg.application.silence_min_ms,
+        );
+
+        self.handle_shortcuts(ctx);
+        self.track_pause_for_resume();
+
+        if let Some(log) = &mut self.event_log {
+            log.observe(&self.player.snapshot(), self.player.loop_region.is_some());
+        }
 
         egui::TopBottomPanel::top("top_bar").show(ctx, |ui| {
             self.ui_top_bar(ui);
@@ -481,17 +2285,97 @@ impl eframe::App for App {
             self.ui_central(ui);
         });
 
-        self.ui_archive_dialog(ctx, frame);
+        self.ui_open_dir_prompt(ctx);
+        self.ui_reset_config_dialog(ctx);
+        self.ui_settings_window(ctx);
+        self.ui_pedal_diagnostics(ctx);
+        self.ui_resume_prompt(ctx);
+        self.ui_archive_dialog(ctx);
+        if self.request_close {
+            frame.close();
+        }
 
         // Request periodic repaints to drive timing and hold-rewind ticks
         ctx.request_repaint_after(std::time::Duration::from_millis(33));
     }
 
     fn on_close_event(&mut self) -> bool {
+        if self.player.playing {
+            self.save_position();
+        }
+        // Tearing down mid-copy could leave a partial file in the archive;
+        // close once the worker has finished instead.
+        if self.archive_job.is_some() {
+            self.archive_pending_exit = true;
+            return false;
+        }
+        if self.restore_job.is_some() {
+            self.restore_pending_exit = true;
+            return false;
+        }
         true
     }
 }
 
+fn configure_player(player: &mut Player, cfg: &Config) {
+    player.force_mono_output = cfg.application.force_mono_output;
+    player.mono_output = cfg.application.mono_output;
+    player.rewind_on_pause_ms = cfg.application.rewind_on_pause_ms;
+    player.sample_accurate = cfg.application.sample_accurate_position;
+    player.speed_ramp_ms = cfg.application.speed_ramp_ms;
+    player.seek_from_committed = cfg.application.seek_base == SeekBase::Committed;
+    player.preserve_pitch = cfg.application.preserve_pitch;
+    player.skip_silence = cfg.application.skip_silence;
+    player.voice_filter = cfg.application.voice_filter;
+    player.highpass_hz = cfg.application.highpass_hz;
+    player.presence_boost = cfg.application.presence_boost;
+    player.fade_ms = cfg.application.fade_ms;
+    player.set_volume(cfg.application.volume);
+}
+
+/// (left, middle, right) key codes from the selected model, else the defaults.
+fn pedal_codes(cfg: &Config) -> (u32, u32, u32) {
+    if let Some(name) = &cfg.input.selected_model {
+        if let Some(model) = cfg.pedals.iter().find(|p| &p.name == name) {
+            return (model.left_code, model.middle_code, model.right_code);
+        }
+        warn!("Selected model '{}' not found; using defaults", name);
+    }
+    (
+        cfg.pedal_defaults.left_code,
+        cfg.pedal_defaults.middle_code,
+        cfg.pedal_defaults.right_code,
+    )
+}
+
+fn archive_shortcut(cfg: &Config) -> Option<egui::KeyboardShortcut> {
+    let shortcut = parse_shortcut(&cfg.application.archive_shortcut);
+    if shortcut.is_none() {
+        warn!(
+            "Invalid archive shortcut '{}'; shortcut disabled",
+            cfg.application.archive_shortcut
+        );
+    }
+    shortcut
+}
+
+fn fallback_keys(cfg: &Config) -> [Option<egui::Key>; 3] {
+    let fallback = &cfg.input.keyboard_fallback;
+    if !fallback.enabled {
+        return [None; 3];
+    }
+    [&fallback.left, &fallback.middle, &fallback.right].map(|name| {
+        if name.trim().is_empty() {
+            return None;
+        }
+        let key = parse_key(name);
+        if key.is_none() {
+            warn!("Invalid fallback key '{}'; ignored", name);
+        }
+        key
+    })
+}
+
 fn init_logger() {
     use env_logger::{Builder, Env};
     let env = Env::default().default_filter_or("info");
@@ -512,7 +2396,22 @@ fn init_logger() {
 fn main() -> eframe::Result<()> {
     init_logger();
 
-    let cfg = Config::load_or_default();
+    let args = CliArgs::parse();
+    if let Some(path) = &args.config {
+        config::init_config_path(path.clone());
+    }
+    config::init_portable(args.portable);
+    let mut cfg = Config::load_or_default();
+    cfg.input.forced_device = args.device.clone();
+
+    if let Some(path) = &args.probe {
+        let ok = probe::run(
+            path,
+            args.json,
+            &DecodeOptions::from_config(&cfg.application),
+        );
+        std::process::exit(if ok { 0 } else { 1 });
+    }
 
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(900.0, 300.0)),
@@ -522,6 +2421,252 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "transcribeupl",
         options,
-        Box::new(|cc| Box::new(App::new(cc, cfg))),
+        Box::new(|cc| Box::new(App::new(cc, cfg, args))),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::tests::decoded;
+
+    /// Defaults, kept away from real pedals and the user's data files.
+    fn test_config() -> Config {
+        let mut cfg = Config::default();
+        cfg.input.forced_device = Some(PathBuf::from("/nonexistent/pedal"));
+        cfg.application.event_log = false;
+        cfg.application.resume_positions = false;
+        cfg.application.archive_ledger = false;
+        cfg
+    }
+
+    /// An app on an idle output with `secs` of stereo at 1 kHz loaded, each
+    /// sample its own interleaved index. Pedal input goes through the
+    /// returned sender, as from the pedal manager, which isn't started.
+    fn test_app(cfg: Config, secs: usize) -> (App, mpsc::Sender<PedalMsg>) {
+        let mut player = Player::idle();
+        configure_player(&mut player, &cfg);
+        player.load_decoded(Path::new("/in/memo.wav"), decoded(secs));
+        let (tx, rx) = mpsc::channel();
+        let app = App::with_player(cfg, player, rx, egui::Context::default());
+        (app, tx)
+    }
+
+    fn pedal(tx: &mpsc::Sender<PedalMsg>, code: u32, value: i32, time: Instant) {
+        tx.send(PedalMsg::Input(PedalEvent { code, value, time }))
+            .unwrap();
+    }
+
+    #[test]
+    fn right_pedal_plays_from_the_pre_roll_and_pauses_where_it_got_to() {
+        let mut cfg = test_config();
+        cfg.application.play_start_rewind_seconds = 2;
+        let (mut app, tx) = test_app(cfg, 10);
+        app.player.seek_to_index(10_000); // 5 s
+        let t0 = Instant::now();
+
+        pedal(&tx, app.right_code, 1, t0);
+        app.drain_pedal_msgs();
+        let snap = app.player.snapshot();
+        assert!(snap.playing);
+        assert_eq!(snap.position_secs, 3.0);
+
+        app.player.output.pull(1_500);
+        pedal(&tx, app.right_code, 0, t0 + Duration::from_secs(1));
+        app.drain_pedal_msgs();
+        let snap = app.player.snapshot();
+        assert!(!snap.playing);
+        assert_eq!(snap.position_samples, 7_500);
+    }
+
+    #[test]
+    fn chatter_inside_the_debounce_window_is_one_transition() {
+        let (mut app, tx) = test_app(test_config(), 10);
+        let ms = |n| Duration::from_millis(n);
+        let t0 = Instant::now();
+        let right = app.right_code;
+        app.player.seek_to_index(10_000); // 5 s
+
+        // Press, then release+press bounces: playing throughout, from the
+        // one pre-roll.
+        for (value, at) in [(1, 0), (0, 3), (1, 6), (0, 9), (1, 12)] {
+            pedal(&tx, right, value, t0 + ms(at));
+        }
+        app.drain_pedal_msgs();
+        app.settle_bounced_pedals(t0 + ms(100));
+        assert!(app.player.playing);
+        assert!(app.bounced.is_empty());
+        assert_eq!(app.player.snapshot().position_secs, 4.0);
+
+        // A release that bounces back up and down again settles as the
+        // release once the window has passed, not before.
+        for (value, at) in [(0, 1_000), (1, 1_004), (0, 1_008)] {
+            pedal(&tx, right, value, t0 + ms(at));
+        }
+        app.drain_pedal_msgs();
+        assert!(!app.player.playing);
+        app.settle_bounced_pedals(t0 + ms(1_010));
+        assert!(!app.player.playing && !app.right_pressed);
+        app.settle_bounced_pedals(t0 + ms(1_100));
+        assert!(!app.player.playing && !app.right_pressed);
+        assert!(app.bounced.is_empty());
+    }
+
+    #[test]
+    fn a_lone_bounce_is_applied_once_the_window_has_passed() {
+        let (mut app, tx) = test_app(test_config(), 10);
+        let ms = |n| Duration::from_millis(n);
+        let t0 = Instant::now();
+        let right = app.right_code;
+
+        // The release comes too soon after the press to be trusted.
+        pedal(&tx, right, 1, t0);
+        pedal(&tx, right, 0, t0 + ms(10));
+        app.drain_pedal_msgs();
+        assert!(app.player.playing);
+        app.settle_bounced_pedals(t0 + ms(20));
+        assert!(app.player.playing);
+        app.settle_bounced_pedals(t0 + ms(30));
+        assert!(!app.player.playing && !app.right_pressed);
+    }
+
+    #[test]
+    fn a_chord_undoes_the_first_pedals_pre_roll() {
+        let mut cfg = test_config();
+        cfg.input.chord_left_right = ChordAction::ArchiveDialog;
+        let (mut app, tx) = test_app(cfg, 10);
+        app.player.seek_to_index(10_000); // 5 s
+        let t0 = Instant::now();
+
+        // Right alone plays from the pre-roll...
+        pedal(&tx, app.right_code, 1, t0);
+        app.drain_pedal_msgs();
+        assert!(app.player.playing);
+        assert_eq!(app.player.snapshot().position_secs, 4.0);
+
+        // ...until left joins within the chord window.
+        pedal(&tx, app.left_code, 1, t0 + Duration::from_millis(50));
+        app.drain_pedal_msgs();
+        assert!(app.show_archive_dialog);
+        assert_eq!(app.archive_resume, Some((10_000, false)));
+        let snap = app.player.snapshot();
+        assert!(!snap.playing);
+        assert_eq!(snap.position_samples, 10_000);
+        assert!(app.hold_last_tick.is_none());
+    }
+
+    /// Poll the archive worker until it has reported back.
+    fn finish_archive(app: &mut App) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while app.archive_job.is_some() {
+            assert!(Instant::now() < deadline, "archive worker hung");
+            thread::sleep(Duration::from_millis(5));
+            app.poll_archive();
+        }
+    }
+
+    #[test]
+    fn exit_with_no_file_loaded_just_quits() {
+        let (mut app, _tx) = test_app(test_config(), 1);
+        app.player.unload();
+        app.open_archive_dialog();
+        app.exit_from_archive_dialog();
+        assert!(app.request_close);
+        assert!(app.archive_job.is_none());
+        assert!(app.archive_error.is_none() && app.errors.is_empty());
+        assert!(eframe::App::on_close_event(&mut app));
+    }
+
+    #[test]
+    fn exit_closes_only_once_the_archive_is_done() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("memo.wav");
+        std::fs::write(&src, b"audio").unwrap();
+        let mut cfg = test_config();
+        cfg.paths.archive_dir = dir.path().join("archive");
+        let (mut app, _tx) = test_app(cfg, 1);
+        app.player.file_path = Some(src.clone());
+
+        app.open_archive_dialog();
+        app.exit_from_archive_dialog();
+        // Pending: the window stays open while the worker runs.
+        assert!(app.archive_pending_exit && !app.request_close);
+        assert!(!eframe::App::on_close_event(&mut app));
+
+        finish_archive(&mut app);
+        assert!(app.request_close && !app.show_archive_dialog);
+        assert!(app.player.file_path.is_none() && !src.exists());
+        assert!(eframe::App::on_close_event(&mut app));
+    }
+
+    #[test]
+    fn a_failed_archive_cancels_the_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let gone = dir.path().join("gone.wav");
+        let mut cfg = test_config();
+        cfg.paths.archive_dir = dir.path().join("archive");
+        cfg.paths.archive_retries = 0;
+        let (mut app, _tx) = test_app(cfg, 1);
+        app.player.file_path = Some(gone.clone());
+
+        app.open_archive_dialog();
+        app.exit_from_archive_dialog();
+        assert!(app.archive_pending_exit);
+        finish_archive(&mut app);
+        assert!(!app.archive_pending_exit && !app.request_close);
+        assert!(app.show_archive_dialog && app.archive_error.is_some());
+        assert_eq!(app.player.file_path, Some(gone));
+    }
+
+    #[test]
+    fn marker_jumps_stop_at_the_ends_unless_they_wrap() {
+        for wrap in [false, true] {
+            let mut cfg = test_config();
+            cfg.application.wrap_markers = wrap;
+            let (mut app, _tx) = test_app(cfg, 10);
+            for s in [2, 5] {
+                app.markers.insert(Duration::from_secs(s), 0);
+            }
+            let at = |app: &App| app.player.snapshot().position_secs.round() as u64;
+
+            app.seek_to_secs(3.0);
+            app.jump_to_marker(true);
+            assert_eq!(at(&app), 5);
+            app.jump_to_marker(true);
+            assert_eq!(at(&app), if wrap { 2 } else { 5 });
+
+            app.seek_to_secs(1.0);
+            app.jump_to_marker(false);
+            assert_eq!(at(&app), if wrap { 5 } else { 1 });
+        }
+    }
+
+    #[test]
+    fn the_resume_position_goes_only_once_the_archive_succeeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let src = root.join("memo.wav");
+        std::fs::write(&src, b"audio").unwrap();
+        let blocker = root.join("not-a-dir");
+        std::fs::write(&blocker, b"").unwrap();
+        let mut cfg = test_config();
+        cfg.paths.archive_dir = blocker.join("archive");
+        cfg.paths.archive_retries = 0;
+        let (mut app, _tx) = test_app(cfg, 1);
+        app.player.file_path = Some(src.clone());
+        let mut store = PositionStore::open(root.join("positions.json"));
+        store.set(&src, 12.0);
+        app.positions = Some(store);
+
+        app.do_archive(false).unwrap();
+        finish_archive(&mut app);
+        assert!(app.archive_error.is_some());
+        assert_eq!(app.positions.as_ref().unwrap().get(&src), Some(12.0));
+
+        app.cfg.paths.archive_dir = root.join("archive");
+        app.do_archive(false).unwrap();
+        finish_archive(&mut app);
+        assert!(app.archive_error.is_none() && !src.exists());
+        assert_eq!(app.positions.as_ref().unwrap().get(&src), None);
+    }
+}
@@ -0,0 +1,176 @@
+//! Position markers set while transcribing, exported as a plain-text
+//! sidecar (`<name>.markers.txt`, one `n<TAB>MM:SS.mmm` line each) that can
+//! be pasted into the transcript. A plain `HH:MM:SS` list of them can also
+//! be saved anywhere for other tools.
+
+use crate::config::Config;
+use crate::fsutil::write_atomic;
+use crate::ui_time::{format_hms, format_span_ms};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Below the data dir; where exports go in read-only mode.
+const READ_ONLY_DIR: &str = "markers";
+
+/// Markers of the loaded file in time order. Past `application.max_bookmarks`
+/// the one added longest ago is dropped to make room.
+#[derive(Debug, Default)]
+pub struct Markers {
+    times: Vec<Duration>,      // sorted
+    added: VecDeque<Duration>, // oldest first
+}
+
+impl Markers {
+    pub fn as_slice(&self) -> &[Duration] {
+        &self.times
+    }
+
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.times.clear();
+        self.added.clear();
+    }
+
+    /// Add a marker at `at` and return its number (0-based, in time order),
+    /// or `None` if there is one there already. `max` of 0 means no cap.
+    pub fn insert(&mut self, at: Duration, max: usize) -> Option<usize> {
+        let i = self.times.partition_point(|m| *m < at);
+        if self.times.get(i) == Some(&at) {
+            return None;
+        }
+        self.times.insert(i, at);
+        self.added.push_back(at);
+        self.prune(max);
+        self.times.binary_search(&at).ok()
+    }
+
+    /// Drop the oldest markers until at most `max` are left (0: no cap).
+    pub fn prune(&mut self, max: usize) {
+        while max > 0 && self.added.len() > max {
+            if let Some(oldest) = self.added.pop_front() {
+                self.times.retain(|m| *m != oldest);
+            }
+        }
+    }
+}
+
+/// `<name>.markers.txt` next to `audio`. In `dir`, which collects exports
+/// from many folders, it is `<name>.<folder hash>.markers.txt` so files of
+/// the same name from different folders keep apart.
+pub fn sidecar_path(audio: &Path, dir: Option<&Path>) -> PathBuf {
+    let Some(dir) = dir else {
+        return audio.with_extension("markers.txt");
+    };
+    let folder = audio.parent().unwrap_or(Path::new(""));
+    let hash = format!("{:x}", Sha256::digest(folder.to_string_lossy().as_bytes()));
+    let stem = audio.file_stem().unwrap_or_default().to_string_lossy();
+    dir.join(format!("{}.{}.markers.txt", stem, &hash[..8]))
+}
+
+/// Write `markers` next to `audio`, replacing an older export. With
+/// `read_only` the source folder is left alone and the sidecar goes to
+/// `markers/` in the data dir instead. Returns the sidecar's path.
+pub fn write_sidecar(
+    audio: &Path,
+    markers: &[Duration],
+    read_only: bool,
+) -> std::io::Result<PathBuf> {
+    let text: String = markers
+        .iter()
+        .enumerate()
+        .map(|(i, m)| format!("{}\t{}\n", i + 1, format_span_ms(m.as_secs_f64())))
+        .collect();
+    let dir = read_only.then(|| Config::data_dir().join(READ_ONLY_DIR));
+    let path = match &dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            // The same file reached by another route gets the same name.
+            let audio = audio.canonicalize().unwrap_or_else(|_| audio.to_path_buf());
+            sidecar_path(&audio, Some(dir))
+        }
+        None => sidecar_path(audio, None),
+    };
+    write_atomic(&path, text.as_bytes())?;
+    Ok(path)
+}
+
+/// One `HH:MM:SS` line per marker, in time order.
+pub fn timestamp_list(markers: &[Duration]) -> String {
+    markers
+        .iter()
+        .map(|m| format!("{}\n", format_hms(m.as_secs())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: &[u64]) -> Vec<Duration> {
+        s.iter().map(|&s| Duration::from_secs(s)).collect()
+    }
+
+    #[test]
+    fn markers_stay_in_time_order_without_repeats() {
+        let mut markers = Markers::default();
+        assert_eq!(markers.insert(Duration::from_secs(30), 0), Some(0));
+        assert_eq!(markers.insert(Duration::from_secs(10), 0), Some(0));
+        assert_eq!(markers.insert(Duration::from_secs(20), 0), Some(1));
+        assert_eq!(markers.insert(Duration::from_secs(20), 0), None);
+        assert_eq!(markers.as_slice(), secs(&[10, 20, 30]));
+    }
+
+    #[test]
+    fn the_oldest_marker_makes_room_past_the_cap() {
+        let mut markers = Markers::default();
+        for s in [30, 10, 20] {
+            markers.insert(Duration::from_secs(s), 3);
+        }
+        assert_eq!(markers.insert(Duration::from_secs(5), 3), Some(0));
+        assert_eq!(markers.as_slice(), secs(&[5, 10, 20]));
+
+        // A lower cap from a config change prunes in the same order.
+        markers.prune(1);
+        assert_eq!(markers.as_slice(), secs(&[5]));
+        markers.clear();
+        assert!(markers.is_empty());
+    }
+
+    #[test]
+    fn timestamp_list_has_one_line_per_marker() {
+        assert_eq!(
+            timestamp_list(&secs(&[5, 754, 3723])),
+            "00:00:05\n00:12:34\n01:02:03\n"
+        );
+        assert_eq!(timestamp_list(&[]), "");
+    }
+
+    #[test]
+    fn sidecar_next_to_the_audio() {
+        assert_eq!(
+            sidecar_path(Path::new("/in/memo.v2.mp3"), None),
+            Path::new("/in/memo.v2.markers.txt")
+        );
+    }
+
+    #[test]
+    fn sidecar_in_another_dir_keeps_same_named_files_apart() {
+        let dir = Path::new("/data/markers");
+        let a = sidecar_path(Path::new("/in/a/memo.mp3"), Some(dir));
+        let b = sidecar_path(Path::new("/in/b/memo.mp3"), Some(dir));
+        assert_ne!(a, b);
+        assert_eq!(a.parent(), Some(dir));
+        let name = a.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("memo.") && name.ends_with(".markers.txt"));
+        assert_eq!(a, sidecar_path(Path::new("/in/a/memo.mp3"), Some(dir)));
+    }
+}
@@ -0,0 +1,22 @@
+use log::warn;
+use notify_rust::Notification;
+use std::thread;
+
+/// Show a desktop notification without blocking the caller; the D-Bus
+/// round trip happens on a short-lived thread.
+pub fn desktop_notify(summary: &str, body: String) {
+    let summary = summary.to_owned();
+    let spawned = thread::Builder::new().name("notify".into()).spawn(move || {
+        if let Err(e) = Notification::new()
+            .appname("transcribeupl")
+            .summary(&summary)
+            .body(&body)
+            .show()
+        {
+            warn!("Desktop notification failed: {}", e);
+        }
+    });
+    if let Err(e) = spawned {
+        warn!("Failed to spawn notification thread: {}", e);
+    }
+}
@@ -1,23 +1,37 @@
 use crate::config::Config;
+use crate::inotify::DirWatch;
 use evdev::Device;
 use log::{debug, info, warn};
+use std::os::fd::AsRawFd;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub enum PedalStatus {
     Scanning,
-    Connected { name: String, path: PathBuf },
-    NotFound,
+    Connected {
+        name: String,
+        path: PathBuf,
+        vendor_id: u16,
+        product_id: u16,
+    },
+    /// No pedal; the next scan happens after `retry_in` unless a device
+    /// appears sooner.
+    NotFound {
+        retry_in: Duration,
+    },
     Error(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct PedalEvent {
-    pub code: u32,  // key code
-    pub value: i32, // 1=press, 0=release, 2=repeat(ignored)
+    pub code: u32,     // key code
+    pub value: i32,    // 1=press, 0=release, 2=repeat(ignored)
+    pub time: Instant, // when the reader thread received it
 }
 
 #[derive(Debug)]
@@ -27,32 +41,54 @@ pub enum PedalMsg {
     Disconnected,
 }
 
+// How long a blocking wait on the device may last before re-checking shutdown.
+const POLL_TIMEOUT_MS: i32 = 250;
+// Minimum gap between `Scanning` statuses, so a flapping cable doesn't
+// make the status line thrash.
+const SCANNING_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
 pub struct PedalManager {
     tx: Sender<PedalMsg>,
+    shutdown: Arc<AtomicBool>,
     _handle: thread::JoinHandle<()>,
 }
 
 impl PedalManager {
     pub fn start(cfg: Config, tx: Sender<PedalMsg>) -> Self {
         let tx_for_struct = tx.clone();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown_for_thread = shutdown.clone();
         let handle = thread::Builder::new()
             .name("pedal-manager".into())
-            .spawn(move || run_manager(cfg, tx))
+            .spawn(move || run_manager(cfg, tx, shutdown_for_thread))
             .expect("Failed to spawn pedal manager");
         Self {
             tx: tx_for_struct,
+            shutdown,
             _handle: handle,
         }
     }
 }
 
+impl Drop for PedalManager {
+    fn drop(&mut self) {
+        // The thread notices within POLL_TIMEOUT_MS and exits.
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
 fn preferred_device_paths(cfg: &Config) -> Vec<Preferred> {
+    // 0) A device forced on the command line replaces all of the below
+    if let Some(p) = &cfg.input.forced_device {
+        return vec![Preferred::Path(p.clone())];
+    }
+
     let mut v = Vec::new();
 
     // 1) Default pedal (highest priority)
     v.push(Preferred::VidPid {
-        vid: 0x0911,
-        pid: 0x1844,
+        vid: cfg.pedal_defaults.vendor_id,
+        pid: cfg.pedal_defaults.product_id,
     });
 
     // 2) Configured pedals in listed order (descending by list order)
@@ -71,17 +107,31 @@ fn preferred_device_paths(cfg: &Config) -> Vec<Preferred> {
     v
 }
 
+#[derive(Debug, PartialEq)]
 enum Preferred {
     VidPid { vid: u16, pid: u16 },
     Path(PathBuf),
 }
 
-fn run_manager(cfg: Config, tx: Sender<PedalMsg>) {
-    let mut last_report = Instant::now() - Duration::from_secs(10);
-    loop {
-        if last_report.elapsed() >= Duration::from_secs(1) {
+fn run_manager(cfg: Config, tx: Sender<PedalMsg>, shutdown: Arc<AtomicBool>) {
+    let min_delay = Duration::from_millis(cfg.input.scan_interval_ms.max(100));
+    let max_delay = Duration::from_millis(cfg.input.scan_backoff_max_ms).max(min_delay);
+    // With change notifications, the timed rescan is only a fallback.
+    let watch = DeviceWatch::new();
+    let mut delay = if watch.is_some() {
+        max_delay
+    } else {
+        min_delay
+    };
+    // Scanning is announced at startup and after a disconnect, not on
+    // every rescan.
+    let mut announce_scan = true;
+    let mut last_announce: Option<Instant> = None;
+    while !shutdown.load(Ordering::Relaxed) {
+        if announce_scan && last_announce.is_none_or(|t| t.elapsed() >= SCANNING_STATUS_INTERVAL) {
             let _ = tx.send(PedalMsg::Status(PedalStatus::Scanning));
-            last_report = Instant::now();
+            last_announce = Some(Instant::now());
+            announce_scan = false;
         }
 
         let prefs = preferred_device_paths(&cfg);
@@ -90,31 +140,205 @@ fn run_manager(cfg: Config, tx: Sender<PedalMsg>) {
         match find_device(&prefs) {
             Ok(Some((path, dev))) => {
                 let name = dev.name().unwrap_or("Unknown").to_string();
+                let (vid, pid) = (dev.input_id().vendor(), dev.input_id().product());
                 let _ = tx.send(PedalMsg::Status(PedalStatus::Connected {
                     name: name.clone(),
                     path: path.clone(),
+                    vendor_id: vid,
+                    product_id: pid,
                 }));
                 info!("Pedal connected: {} @ {}", name, path.display());
 
-                // Read events until disconnect/error
-                if let Err(e) = read_events_loop(dev, &tx) {
-                    warn!("Pedal disconnected or error: {}", e);
+                // Read events until disconnect/error/shutdown, riding out
+                // transient errors by reopening the same device.
+                let mut dev = dev;
+                loop {
+                    match read_events_loop(dev, &path, cfg.input.grab_device, &tx, &shutdown) {
+                        Ok(()) => return, // shutdown requested
+                        Err(e) => warn!("Pedal read error: {}", e),
+                    }
+                    match reopen_within_grace(&path, vid, pid, &cfg, &shutdown) {
+                        Some(d) => {
+                            info!("Pedal reopened after read error: {}", path.display());
+                            dev = d;
+                        }
+                        None => break,
+                    }
                 }
+                if shutdown.load(Ordering::Relaxed) {
+                    return;
+                }
+                warn!("Pedal disconnected: {}", path.display());
                 let _ = tx.send(PedalMsg::Disconnected);
                 // Back to scanning
+                announce_scan = true;
+                if watch.is_none() {
+                    delay = min_delay;
+                }
             }
             Ok(None) => {
-                let _ = tx.send(PedalMsg::Status(PedalStatus::NotFound));
-                thread::sleep(Duration::from_millis(2000));
+                let _ = tx.send(PedalMsg::Status(PedalStatus::NotFound { retry_in: delay }));
+                match &watch {
+                    Some(watch) => {
+                        if watch.wait(delay, &shutdown) {
+                            debug!("Input devices changed; rescanning");
+                        }
+                    }
+                    None => {
+                        if wait_for_device_change(delay, &shutdown) {
+                            debug!("Input devices changed; rescanning");
+                            delay = min_delay;
+                        } else {
+                            delay = (delay * 2).min(max_delay);
+                        }
+                    }
+                }
             }
             Err(e) => {
                 let _ = tx.send(PedalMsg::Status(PedalStatus::Error(e.to_string())));
-                thread::sleep(Duration::from_millis(2000));
+                sleep_unless_shutdown(min_delay, &shutdown);
+            }
+        }
+    }
+    debug!("Pedal manager stopped");
+}
+
+fn sleep_unless_shutdown(dur: Duration, shutdown: &AtomicBool) {
+    let deadline = Instant::now() + dur;
+    while !shutdown.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(POLL_TIMEOUT_MS as u64)));
+    }
+}
+
+/// Retry opening `path` with a growing delay, accepting it only if it is
+/// still the same model. `None` once the retries are used up.
+fn reopen_within_grace(
+    path: &Path,
+    vid: u16,
+    pid: u16,
+    cfg: &Config,
+    shutdown: &AtomicBool,
+) -> Option<Device> {
+    for attempt in 1..=cfg.input.reconnect_retries {
+        let wait = Duration::from_millis(cfg.input.reconnect_retry_ms * attempt as u64);
+        sleep_unless_shutdown(wait, shutdown);
+        if shutdown.load(Ordering::Relaxed) {
+            return None;
+        }
+        match Device::open(path) {
+            Ok(dev) if dev.input_id().vendor() == vid && dev.input_id().product() == pid => {
+                return Some(dev);
+            }
+            Ok(_) => {
+                debug!("{} is now a different device", path.display());
+                return None;
+            }
+            Err(e) => debug!(
+                "Reopen attempt {} of {} failed: {}",
+                attempt,
+                path.display(),
+                e
+            ),
+        }
+    }
+    None
+}
+
+/// Names in /dev/input; listing them doesn't open (and wake) any device.
+fn input_dev_names() -> Vec<std::ffi::OsString> {
+    let mut names: Vec<_> = std::fs::read_dir("/dev/input")
+        .map(|rd| rd.flatten().map(|e| e.file_name()).collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// inotify watch on /dev/input: wakes on nodes being added or removed,
+/// and on udev fixing up a new node's permissions after creating it.
+struct DeviceWatch {
+    watch: DirWatch,
+}
+
+impl DeviceWatch {
+    /// `None` (and a warning) if inotify isn't available; callers fall
+    /// back to polling the directory.
+    fn new() -> Option<Self> {
+        let mask = libc::IN_CREATE | libc::IN_DELETE | libc::IN_ATTRIB | libc::IN_MOVED_TO;
+        match DirWatch::new(Path::new("/dev/input"), mask) {
+            Ok(watch) => Some(Self { watch }),
+            Err(e) => {
+                warn!("Cannot watch /dev/input ({}); polling instead", e);
+                None
+            }
+        }
+    }
+
+    /// Sleep up to `dur`, returning early with `true` on a change.
+    fn wait(&self, dur: Duration, shutdown: &AtomicBool) -> bool {
+        let deadline = Instant::now() + dur;
+        while !shutdown.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            let slice = (deadline - now).min(Duration::from_millis(POLL_TIMEOUT_MS as u64));
+            if self.watch.wait(slice) {
+                // Any of them just means "rescan".
+                self.watch.read_names();
+                return true;
             }
         }
+        false
     }
 }
 
+/// Sleep up to `dur`, returning early with `true` if the set of input
+/// device nodes changes (something was plugged in or removed).
+fn wait_for_device_change(dur: Duration, shutdown: &AtomicBool) -> bool {
+    let before = input_dev_names();
+    let deadline = Instant::now() + dur;
+    while !shutdown.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(POLL_TIMEOUT_MS as u64)));
+        if input_dev_names() != before {
+            return true;
+        }
+    }
+    false
+}
+
+/// An input device as listed for diagnostics.
+#[derive(Debug, Clone)]
+pub struct InputDeviceInfo {
+    pub path: PathBuf,
+    pub name: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+/// Every readable input device with keys, whether or not it matches a
+/// configured pedal.
+pub fn input_devices() -> Vec<InputDeviceInfo> {
+    let mut devices: Vec<InputDeviceInfo> = evdev::enumerate()
+        .filter(|(_, dev)| dev.supported_keys().is_some())
+        .map(|(path, dev)| InputDeviceInfo {
+            name: dev.name().unwrap_or("Unknown").to_string(),
+            vendor_id: dev.input_id().vendor(),
+            product_id: dev.input_id().product(),
+            path,
+        })
+        .collect();
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    devices
+}
+
 fn find_device(prefs: &[Preferred]) -> anyhow::Result<Option<(PathBuf, Device)>> {
     // Snapshot of /dev/input event devices
     let devices: Vec<(PathBuf, Device)> = evdev::enumerate().collect();
@@ -151,8 +375,63 @@ fn find_device(prefs: &[Preferred]) -> anyhow::Result<Option<(PathBuf, Device)>>
     Ok(None)
 }
 
-fn read_events_loop(mut dev: Device, tx: &Sender<PedalMsg>) -> anyhow::Result<()> {
+/// Read events until the device errors/vanishes (`Err`) or shutdown is
+/// requested (`Ok`), holding an exclusive grab meanwhile if `grab`.
+fn read_events_loop(
+    mut dev: Device,
+    path: &Path,
+    grab: bool,
+    tx: &Sender<PedalMsg>,
+    shutdown: &AtomicBool,
+) -> anyhow::Result<()> {
+    if grab {
+        grab_device(&mut dev, path);
+    }
+    let res = pump_events(&mut dev, path, tx, shutdown);
+    if grab {
+        // Fails harmlessly once the device is gone.
+        let _ = dev.ungrab();
+    }
+    res
+}
+
+/// Take the device for ourselves so its key codes don't also reach the
+/// focused app. Reading works without it, so failure only warns.
+fn grab_device(dev: &mut Device, path: &Path) {
+    match dev.grab() {
+        Ok(()) => debug!("Grabbed {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => warn!(
+            "Cannot grab {} (permission denied); pedal presses will also \
+             type into other apps. Check the udev rule for the device.",
+            path.display()
+        ),
+        Err(e) if e.raw_os_error() == Some(libc::EBUSY) => warn!(
+            "Cannot grab {}: another program holds it exclusively",
+            path.display()
+        ),
+        Err(e) => warn!("Cannot grab {}: {}", path.display(), e),
+    }
+}
+
+/// Never blocks longer than `POLL_TIMEOUT_MS`, so a device that goes
+/// silent without a hangup is still noticed via its node vanishing.
+fn pump_events(
+    dev: &mut Device,
+    path: &Path,
+    tx: &Sender<PedalMsg>,
+    shutdown: &AtomicBool,
+) -> anyhow::Result<()> {
     loop {
+        if shutdown.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if !wait_readable(dev, POLL_TIMEOUT_MS)? {
+            // Heartbeat: a stuck device whose node is gone won't wake poll().
+            if !path.exists() {
+                return Err(anyhow::anyhow!("device node {} vanished", path.display()));
+            }
+            continue;
+        }
         match dev.fetch_events() {
             Ok(events) => {
                 for ev in events {
@@ -163,6 +442,7 @@ fn read_events_loop(mut dev: Device, tx: &Sender<PedalMsg>) -> anyhow::Result<()
                         let _ = tx.send(PedalMsg::Input(PedalEvent {
                             code: code_u16 as u32,
                             value: v,
+                            time: Instant::now(),
                         }));
                     }
                 }
@@ -174,3 +454,76 @@ fn read_events_loop(mut dev: Device, tx: &Sender<PedalMsg>) -> anyhow::Result<()
         }
     }
 }
+
+/// Wait up to `timeout_ms` for `dev` to have events. `Ok(false)` on timeout.
+fn wait_readable(dev: &Device, timeout_ms: i32) -> std::io::Result<bool> {
+    let mut pfd = libc::pollfd {
+        fd: dev.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `pfd` is a valid pollfd for the duration of the call and the
+    // fd is owned by `dev`, which outlives it.
+    let rc = unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+    if rc < 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() == std::io::ErrorKind::Interrupted {
+            return Ok(false);
+        }
+        return Err(err);
+    }
+    if pfd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::BrokenPipe,
+            "device hung up",
+        ));
+    }
+    Ok(rc > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PedalModel;
+
+    #[test]
+    fn preferred_devices_follow_the_configured_defaults() {
+        let mut cfg = Config::default();
+        cfg.pedal_defaults.vendor_id = 0x1234;
+        cfg.pedal_defaults.product_id = 0xabcd;
+        cfg.pedals = vec![PedalModel {
+            name: "Other".into(),
+            vendor_id: 0x05f3,
+            product_id: 0x00ff,
+            left_code: 1,
+            middle_code: 2,
+            right_code: 3,
+        }];
+        cfg.input.device_path = Some(PathBuf::from("/dev/input/event7"));
+        assert_eq!(
+            preferred_device_paths(&cfg),
+            [
+                Preferred::VidPid {
+                    vid: 0x1234,
+                    pid: 0xabcd
+                },
+                Preferred::VidPid {
+                    vid: 0x05f3,
+                    pid: 0x00ff
+                },
+                Preferred::Path(PathBuf::from("/dev/input/event7")),
+            ]
+        );
+    }
+
+    #[test]
+    fn forced_device_replaces_the_preferred_list() {
+        let mut cfg = Config::default();
+        cfg.input.device_path = Some(PathBuf::from("/dev/input/event7"));
+        cfg.input.forced_device = Some(PathBuf::from("/dev/input/event3"));
+        assert_eq!(
+            preferred_device_paths(&cfg),
+            [Preferred::Path(PathBuf::from("/dev/input/event3"))]
+        );
+    }
+}
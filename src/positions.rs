@@ -0,0 +1,145 @@
+//! Last playback position per file (`application.resume_positions`), kept
+//! in `positions.json` in the data dir and keyed by canonical path.
+
+use crate::config::Config;
+use crate::fsutil::write_atomic;
+use log::{error, warn};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+const POSITIONS_FILE: &str = "positions.json";
+
+pub struct PositionStore {
+    path: PathBuf,
+    // Seconds rather than sample indices, which change with resampling.
+    entries: BTreeMap<String, f64>,
+    // Saves are written on a worker thread, so a pause never waits for
+    // the disk; `None` if it couldn't be started.
+    writer: Option<(Sender<Vec<u8>>, JoinHandle<()>)>,
+}
+
+impl PositionStore {
+    pub fn load() -> Self {
+        Self::open(Config::data_dir().join(POSITIONS_FILE))
+    }
+
+    /// The store kept in `path`, starting empty if it is missing.
+    pub fn open(path: PathBuf) -> Self {
+        let entries = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        let writer = spawn_writer(path.clone())
+            .map_err(|e| error!("Failed to spawn positions thread: {}", e))
+            .ok();
+        Self {
+            path,
+            entries,
+            writer,
+        }
+    }
+
+    pub fn get(&self, file: &Path) -> Option<f64> {
+        self.entries.get(&key(file)).copied()
+    }
+
+    pub fn set(&mut self, file: &Path, secs: f64) {
+        let k = key(file);
+        if self.entries.get(&k) == Some(&secs) {
+            return;
+        }
+        self.entries.insert(k, secs);
+        self.save();
+    }
+
+    /// Forget the entry under `key`, see [`key`].
+    pub fn remove(&mut self, key: &str) {
+        if self.entries.remove(key).is_some() {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let json = match serde_json::to_vec_pretty(&self.entries) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Saving {} failed: {}", self.path.display(), e);
+                return;
+            }
+        };
+        let json = match &self.writer {
+            Some((tx, _)) => match tx.send(json) {
+                Ok(()) => return,
+                Err(unsent) => unsent.0,
+            },
+            None => json,
+        };
+        write_file(&self.path, &json);
+    }
+}
+
+impl Drop for PositionStore {
+    /// Wait for the last save to reach the disk.
+    fn drop(&mut self) {
+        if let Some((tx, handle)) = self.writer.take() {
+            drop(tx);
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Write whatever was saved last; older saves still queued are skipped.
+fn spawn_writer(path: PathBuf) -> std::io::Result<(Sender<Vec<u8>>, JoinHandle<()>)> {
+    let (tx, rx) = mpsc::channel::<Vec<u8>>();
+    let handle = thread::Builder::new()
+        .name("positions".into())
+        .spawn(move || {
+            while let Ok(mut json) = rx.recv() {
+                while let Ok(newer) = rx.try_recv() {
+                    json = newer;
+                }
+                write_file(&path, &json);
+            }
+        })?;
+    Ok((tx, handle))
+}
+
+fn write_file(path: &Path, json: &[u8]) {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let res = std::fs::create_dir_all(dir).and_then(|_| write_atomic(path, json));
+    if let Err(e) = res {
+        warn!("Saving {} failed: {}", path.display(), e);
+    }
+}
+
+/// Canonical path as a string; falls back to the path as given once the
+/// file is gone, so take it before moving the file.
+pub fn key(file: &Path) -> String {
+    file.canonicalize()
+        .unwrap_or_else(|_| file.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_last_save_is_on_disk_once_the_store_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("positions.json");
+        let memo = dir.path().join("memo.mp3");
+        let mut store = PositionStore::open(path.clone());
+        for secs in 1..=200 {
+            store.set(&memo, secs as f64);
+        }
+        drop(store);
+        assert_eq!(PositionStore::open(path).get(&memo), Some(200.0));
+    }
+}
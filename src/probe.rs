@@ -0,0 +1,69 @@
+use crate::audio::{decode_to_f32_interleaved, DecodeOptions};
+use crate::ui_time::format_span;
+use serde::Serialize;
+use std::path::Path;
+
+/// Result of `--probe`, printed as text or JSON.
+#[derive(Debug, Default, Serialize)]
+struct ProbeReport {
+    file: String,
+    ok: bool,
+    codec: Option<&'static str>,
+    sample_rate: Option<u32>,
+    channels: Option<u16>,
+    duration_secs: Option<f64>,
+    skipped_packets: Option<usize>,
+    error: Option<String>,
+}
+
+/// Decode `path` the way the player would and print a summary. Returns
+/// `false` if decoding failed.
+pub fn run(path: &Path, json: bool, opts: &DecodeOptions) -> bool {
+    let mut report = ProbeReport {
+        file: path.display().to_string(),
+        ..Default::default()
+    };
+    match decode_to_f32_interleaved(path, opts) {
+        Ok(audio) => {
+            let frames = audio.total_samples / audio.channels.max(1) as usize;
+            report.ok = true;
+            report.codec = Some(audio.codec);
+            report.sample_rate = Some(audio.sample_rate);
+            report.channels = Some(audio.channels);
+            report.duration_secs = Some(frames as f64 / audio.sample_rate as f64);
+            report.skipped_packets = Some(audio.skipped_packets);
+        }
+        Err(e) => report.error = Some(format!("{:#}", e)),
+    }
+
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(s) => println!("{}", s),
+            Err(e) => eprintln!("Failed to serialize probe report: {}", e),
+        }
+    } else {
+        print_text(&report);
+    }
+    report.ok
+}
+
+fn print_text(r: &ProbeReport) {
+    println!("file:        {}", r.file);
+    if let Some(err) = &r.error {
+        println!("decode:      FAILED: {}", err);
+        return;
+    }
+    println!("codec:       {}", r.codec.unwrap_or("unknown"));
+    println!("sample rate: {} Hz", r.sample_rate.unwrap_or(0));
+    println!("channels:    {}", r.channels.unwrap_or(0));
+    let secs = r.duration_secs.unwrap_or(0.0);
+    println!(
+        "duration:    {} ({:.3} s)",
+        format_span(secs.floor() as u64),
+        secs
+    );
+    match r.skipped_packets.unwrap_or(0) {
+        0 => println!("decode:      ok"),
+        n => println!("decode:      ok with {} skipped packet(s)", n),
+    }
+}
@@ -0,0 +1,210 @@
+//! The Settings window: an editable copy of the common config fields,
+//! checked before it is written back with `Config::save()`.
+
+use crate::audio::output_device_names;
+use crate::config::Config;
+use eframe::egui;
+use egui::Color32;
+use rfd::FileDialog;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+const REWIND_RANGE: RangeInclusive<u64> = 1..=600;
+const FORWARD_RANGE: RangeInclusive<u64> = 1..=600;
+const HOLD_INTERVAL_RANGE: RangeInclusive<u64> = 50..=10_000;
+const PLAY_START_REWIND_RANGE: RangeInclusive<u64> = 0..=60;
+
+/// The form's free-text fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextField {
+    RewindSeconds,
+    ForwardSeconds,
+    HoldRewindIntervalMs,
+    PlayStartRewindSeconds,
+    DefaultOpenDir,
+}
+
+/// Field values as typed, so a half-edited number can be shown and
+/// reported instead of silently snapping back.
+pub struct SettingsForm {
+    rewind_seconds: String,
+    forward_seconds: String,
+    hold_rewind_interval_ms: String,
+    play_start_rewind_seconds: String,
+    default_open_dir: String,
+    selected_model: Option<String>,
+    clock_millis: bool,
+    output_device: Option<String>,
+    output_devices: Vec<String>, // listed once when the window opens
+    errors: Vec<(TextField, String)>,
+}
+
+impl SettingsForm {
+    pub fn new(cfg: &Config) -> Self {
+        let app = &cfg.application;
+        Self {
+            rewind_seconds: app.rewind_seconds.to_string(),
+            forward_seconds: app.forward_seconds.to_string(),
+            hold_rewind_interval_ms: app.hold_rewind_interval_ms.to_string(),
+            play_start_rewind_seconds: app.play_start_rewind_seconds.to_string(),
+            default_open_dir: cfg.paths.default_open_dir.display().to_string(),
+            selected_model: cfg.input.selected_model.clone(),
+            clock_millis: app.clock_millis,
+            output_device: cfg.audio.output_device.clone(),
+            output_devices: output_device_names(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Draw the fields; `models` are the names offered for the pedal.
+    pub fn ui(&mut self, ui: &mut egui::Ui, models: &[String]) {
+        egui::Grid::new("settings_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                self.number_row(ui, "Rewind (s)", TextField::RewindSeconds, REWIND_RANGE);
+                self.number_row(ui, "Forward (s)", TextField::ForwardSeconds, FORWARD_RANGE);
+                self.number_row(
+                    ui,
+                    "Hold-rewind interval (ms)",
+                    TextField::HoldRewindIntervalMs,
+                    HOLD_INTERVAL_RANGE,
+                );
+                self.number_row(
+                    ui,
+                    "Rewind on play (s)",
+                    TextField::PlayStartRewindSeconds,
+                    PLAY_START_REWIND_RANGE,
+                );
+
+                ui.label("Default open folder");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.default_open_dir);
+                    if ui.button("Browse…").clicked() {
+                        let mut dialog = FileDialog::new();
+                        let current = PathBuf::from(&self.default_open_dir);
+                        if current.is_dir() {
+                            dialog = dialog.set_directory(current);
+                        }
+                        if let Some(dir) = dialog.pick_folder() {
+                            self.default_open_dir = dir.display().to_string();
+                        }
+                    }
+                });
+                ui.end_row();
+                self.error_row(ui, TextField::DefaultOpenDir);
+
+                ui.label("Pedal model");
+                egui::ComboBox::from_id_source("settings_model")
+                    .selected_text(
+                        self.selected_model
+                            .clone()
+                            .unwrap_or_else(|| "(defaults)".to_owned()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.selected_model, None, "(defaults)");
+                        for name in models {
+                            ui.selectable_value(&mut self.selected_model, Some(name.clone()), name);
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Clock");
+                ui.checkbox(&mut self.clock_millis, "Show milliseconds");
+                ui.end_row();
+
+                ui.label("Audio output");
+                egui::ComboBox::from_id_source("settings_output")
+                    .selected_text(
+                        self.output_device
+                            .clone()
+                            .unwrap_or_else(|| "(system default)".to_owned()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.output_device, None, "(system default)");
+                        for name in &self.output_devices {
+                            ui.selectable_value(&mut self.output_device, Some(name.clone()), name);
+                        }
+                    });
+                ui.end_row();
+            });
+    }
+
+    fn number_row(
+        &mut self,
+        ui: &mut egui::Ui,
+        label: &str,
+        field: TextField,
+        range: RangeInclusive<u64>,
+    ) {
+        ui.label(label);
+        ui.add(egui::TextEdit::singleline(self.text_mut(field)).desired_width(80.0))
+            .on_hover_text(format!("{} to {}", range.start(), range.end()));
+        ui.end_row();
+        self.error_row(ui, field);
+    }
+
+    fn error_row(&self, ui: &mut egui::Ui, field: TextField) {
+        if let Some((_, msg)) = self.errors.iter().find(|(f, _)| *f == field) {
+            ui.label("");
+            ui.colored_label(Color32::RED, msg);
+            ui.end_row();
+        }
+    }
+
+    fn text_mut(&mut self, field: TextField) -> &mut String {
+        match field {
+            TextField::RewindSeconds => &mut self.rewind_seconds,
+            TextField::ForwardSeconds => &mut self.forward_seconds,
+            TextField::HoldRewindIntervalMs => &mut self.hold_rewind_interval_ms,
+            TextField::PlayStartRewindSeconds => &mut self.play_start_rewind_seconds,
+            TextField::DefaultOpenDir => &mut self.default_open_dir,
+        }
+    }
+
+    /// `cfg` with the form's values, or `None` (and the errors shown
+    /// inline) if any field is out of range.
+    pub fn apply_to(&mut self, cfg: &Config) -> Option<Config> {
+        self.errors.clear();
+        let rewind = self.parse(TextField::RewindSeconds, REWIND_RANGE);
+        let forward = self.parse(TextField::ForwardSeconds, FORWARD_RANGE);
+        let hold = self.parse(TextField::HoldRewindIntervalMs, HOLD_INTERVAL_RANGE);
+        let play_start = self.parse(TextField::PlayStartRewindSeconds, PLAY_START_REWIND_RANGE);
+        let dir = self.default_open_dir.trim();
+        if dir.is_empty() {
+            self.errors
+                .push((TextField::DefaultOpenDir, "Choose a folder".to_owned()));
+        }
+        if !self.errors.is_empty() {
+            return None;
+        }
+
+        let mut cfg = cfg.clone();
+        cfg.application.rewind_seconds = rewind? as u32;
+        cfg.application.forward_seconds = forward? as u32;
+        cfg.application.hold_rewind_interval_ms = hold?;
+        cfg.application.play_start_rewind_seconds = play_start? as u32;
+        cfg.paths.default_open_dir = PathBuf::from(dir);
+        cfg.input.selected_model = self.selected_model.clone();
+        cfg.application.clock_millis = self.clock_millis;
+        cfg.audio.output_device = self.output_device.clone();
+        Some(cfg)
+    }
+
+    fn parse(&mut self, field: TextField, range: RangeInclusive<u64>) -> Option<u64> {
+        let text = self.text_mut(field).trim().to_owned();
+        match text.parse::<u64>() {
+            Ok(v) if range.contains(&v) => Some(v),
+            _ => {
+                self.errors.push((
+                    field,
+                    format!(
+                        "Enter a whole number from {} to {}",
+                        range.start(),
+                        range.end()
+                    ),
+                ));
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! Decode-on-demand sample store for files too large to hold in memory.
+//!
+//! Audio is decoded in one-second blocks by a worker thread, which keeps
+//! the blocks just behind and a few seconds ahead of the playhead. Readers
+//! take whole blocks and never decode themselves: a block that isn't ready
+//! yet reads as silence unless the caller chooses to wait for it.
+//! Continuing past the last decoded block keeps the decoder going,
+//! anything else seeks the format reader first.
+
+use log::{debug, warn};
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::Decoder;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatReader, SeekMode, SeekTo};
+
+// Blocks decoded ahead of the playhead, and kept behind it for short
+// rewinds and filter warmup; about this many seconds each way.
+pub const PREFETCH_AHEAD: usize = 8;
+const KEEP_BEHIND: usize = 4;
+
+pub struct StreamStore {
+    len: usize,       // interleaved samples in the whole file
+    block_len: usize, // interleaved samples per block
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    cache: Mutex<Cache>,
+    wake: Condvar,  // the worker: the playhead moved or the store closed
+    ready: Condvar, // waiters: a block was decoded
+}
+
+struct Cache {
+    blocks: HashMap<usize, Arc<Vec<f32>>>,
+    playhead: usize, // block the prefetch window is centred on
+    closed: bool,
+}
+
+/// Owned by the worker thread; only it touches the reader and decoder.
+struct Decoding {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    block_len: usize,
+    channels: usize,
+    // Block the decoder continues into without seeking, with samples it
+    // already produced past the end of the previous block.
+    next_block: Option<usize>,
+    carry: Vec<f32>,
+    sample_buf: Option<SampleBuffer<f32>>,
+}
+
+impl std::fmt::Debug for StreamStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamStore")
+            .field("len", &self.len)
+            .field("block_len", &self.block_len)
+            .finish()
+    }
+}
+
+impl StreamStore {
+    /// Start the decode worker. `format` must be positioned at the start
+    /// of the stream, and the track's timestamps must count frames.
+    pub fn new(
+        format: Box<dyn FormatReader>,
+        decoder: Box<dyn Decoder>,
+        track_id: u32,
+        sample_rate: u32,
+        channels: usize,
+        frames: usize,
+    ) -> std::io::Result<Self> {
+        let len = frames * channels;
+        let block_len = sample_rate as usize * channels;
+        let shared = Arc::new(Shared {
+            cache: Mutex::new(Cache {
+                blocks: HashMap::new(),
+                playhead: 0,
+                closed: false,
+            }),
+            wake: Condvar::new(),
+            ready: Condvar::new(),
+        });
+        let decoding = Decoding {
+            format,
+            decoder,
+            track_id,
+            block_len,
+            channels,
+            next_block: Some(0),
+            carry: Vec::new(),
+            sample_buf: None,
+        };
+        let blocks = len.div_ceil(block_len.max(1));
+        let worker = shared.clone();
+        thread::Builder::new()
+            .name("stream".into())
+            .spawn(move || decoding.run(&worker, blocks))?;
+        Ok(Self {
+            len,
+            block_len,
+            shared,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Interleaved samples per block; block `n` starts at `n * block_len`.
+    pub fn block_len(&self) -> usize {
+        self.block_len
+    }
+
+    /// Block `block` if it is decoded, moving the prefetch window to it.
+    /// Never waits: this is what playback reads from the audio callback,
+    /// so a contended lock counts as not ready.
+    pub fn block(&self, block: usize) -> Option<Arc<Vec<f32>>> {
+        let mut cache = self.shared.cache.try_lock()?;
+        if cache.playhead != block {
+            cache.playhead = block;
+            self.shared.wake.notify_one();
+        }
+        cache.blocks.get(&block).cloned()
+    }
+
+    /// Block `block` if it is decoded, leaving the prefetch window alone.
+    pub fn cached(&self, block: usize) -> Option<Arc<Vec<f32>>> {
+        self.shared.cache.lock().blocks.get(&block).cloned()
+    }
+
+    /// Move the prefetch window to `block` and wait up to `timeout` for
+    /// it to be decoded. For the player before it starts a source, so
+    /// playback doesn't open on a gap; never call it on the audio thread.
+    pub fn wait_for(&self, block: usize, timeout: Duration) -> bool {
+        let mut cache = self.shared.cache.lock();
+        if cache.playhead != block {
+            cache.playhead = block;
+            self.shared.wake.notify_one();
+        }
+        let deadline = Instant::now() + timeout;
+        while !cache.blocks.contains_key(&block) {
+            if self
+                .shared
+                .ready
+                .wait_until(&mut cache, deadline)
+                .timed_out()
+            {
+                return cache.blocks.contains_key(&block);
+            }
+        }
+        true
+    }
+}
+
+impl Drop for StreamStore {
+    fn drop(&mut self) {
+        self.shared.cache.lock().closed = true;
+        self.shared.wake.notify_one();
+    }
+}
+
+impl Cache {
+    /// Next block to decode: ahead of the playhead first, then behind it.
+    fn missing(&self, blocks: usize) -> Option<usize> {
+        let ahead = self.playhead..(self.playhead + PREFETCH_AHEAD).min(blocks);
+        let behind = (self.playhead.saturating_sub(KEEP_BEHIND)..self.playhead).rev();
+        ahead.chain(behind).find(|b| !self.blocks.contains_key(b))
+    }
+
+    fn evict_outside_window(&mut self) {
+        let (head, behind) = (self.playhead, KEEP_BEHIND);
+        self.blocks
+            .retain(|&b, _| b + behind >= head && b < head + PREFETCH_AHEAD);
+    }
+}
+
+impl Decoding {
+    fn run(mut self, shared: &Shared, blocks: usize) {
+        loop {
+            let block = {
+                let mut cache = shared.cache.lock();
+                loop {
+                    if cache.closed {
+                        return;
+                    }
+                    if let Some(block) = cache.missing(blocks) {
+                        break block;
+                    }
+                    shared.wake.wait(&mut cache);
+                }
+            };
+            // Decode without the lock so readers are never held up by it.
+            let samples = Arc::new(self.decode_block(block));
+            let mut cache = shared.cache.lock();
+            cache.blocks.insert(block, samples);
+            cache.evict_outside_window();
+            drop(cache);
+            shared.ready.notify_all();
+        }
+    }
+
+    fn decode_block(&mut self, block: usize) -> Vec<f32> {
+        let (block_len, channels) = (self.block_len, self.channels);
+        let start_frame = (block * block_len / channels) as u64;
+        let mut out = Vec::with_capacity(block_len);
+        if self.next_block == Some(block) {
+            out.append(&mut self.carry);
+        } else {
+            self.carry.clear();
+            debug!("Stream seek to frame {}", start_frame);
+            let seeked = self.format.seek(
+                SeekMode::Accurate,
+                SeekTo::TimeStamp {
+                    ts: start_frame,
+                    track_id: self.track_id,
+                },
+            );
+            if let Err(e) = seeked {
+                warn!("Stream seek to frame {} failed: {}", start_frame, e);
+                self.next_block = None;
+                return out;
+            }
+            self.decoder.reset();
+        }
+
+        while out.len() < block_len {
+            let packet = match self.format.next_packet() {
+                Ok(p) => p,
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    break
+                }
+                Err(e) => {
+                    warn!("Stream read failed: {}", e);
+                    break;
+                }
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let packet_ts = packet.ts();
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(a) => a,
+                Err(SymphoniaError::DecodeError(e)) => {
+                    warn!("Decode error (skipping packet): {e}");
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Stream decode failed: {}", e);
+                    break;
+                }
+            };
+            let spec = *decoded.spec();
+            if self
+                .sample_buf
+                .as_ref()
+                .map(|b| b.capacity() < decoded.capacity())
+                .unwrap_or(true)
+            {
+                self.sample_buf = Some(SampleBuffer::<f32>::new(decoded.capacity() as u64, spec));
+            }
+            let sbuf = self.sample_buf.as_mut().expect("just allocated");
+            sbuf.copy_interleaved_ref(decoded);
+            let mut samples = sbuf.samples();
+            // After a seek the first packets may start before the block.
+            if out.is_empty() && packet_ts < start_frame {
+                let skip = ((start_frame - packet_ts) as usize * channels).min(samples.len());
+                samples = &samples[skip..];
+            }
+            out.extend_from_slice(samples);
+        }
+
+        if out.len() > block_len {
+            self.carry = out.split_off(block_len);
+        }
+        self.next_block = Some(block + 1);
+        out
+    }
+}
@@ -0,0 +1,209 @@
+//! Pitch-preserving time-stretch (WSOLA) as a rodio source wrapper.
+//!
+//! The inner source plays at its natural rate; this wrapper reads it
+//! `speed` times faster than it writes, overlap-adding Hann-windowed
+//! segments whose start is nudged (within `SEEK_MS`) to where they best
+//! line up with the previous segment, so the waveform stays continuous and
+//! the pitch unchanged.
+
+use rodio::Source;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Segment length; long enough to hold a few speech pitch periods.
+const WINDOW_MS: u32 = 30;
+// How far a segment may be moved to find the best overlap.
+const SEEK_MS: u32 = 8;
+// Correlate on every Nth frame; plenty for speech and much cheaper.
+const CORR_STRIDE: usize = 2;
+
+pub struct TimeStretch<S> {
+    inner: S,
+    inner_done: bool,
+    channels: usize,
+    sample_rate: u32,
+    speed: f64,
+
+    window: Vec<f32>, // periodic Hann, `win` frames
+    win: usize,       // frames
+    hop: usize,       // synthesis hop = win / 2 (frames)
+    seek: usize,      // frames
+
+    input: VecDeque<f32>,   // interleaved; frame 0 is `input_start`
+    input_start: usize,     // absolute input frame of input[0]
+    nominal: f64,           // ideal absolute start of the next segment
+    natural: Option<usize>, // where the previous segment would continue
+    overlap: Vec<f32>,      // second half of the previous segment, windowed
+    out: VecDeque<f32>,
+    finished: bool,
+}
+
+impl<S: Source<Item = f32>> TimeStretch<S> {
+    pub fn new(inner: S, speed: f32) -> Self {
+        let channels = inner.channels().max(1) as usize;
+        let sample_rate = inner.sample_rate();
+        let frames_for = |ms: u32| (sample_rate as usize * ms as usize / 1000).max(2);
+        let win = frames_for(WINDOW_MS) & !1;
+        let window = (0..win)
+            .map(|i| {
+                let x = std::f32::consts::TAU * i as f32 / win as f32;
+                0.5 - 0.5 * x.cos()
+            })
+            .collect();
+        Self {
+            inner,
+            inner_done: false,
+            channels,
+            sample_rate,
+            speed: speed.max(0.1) as f64,
+            window,
+            win,
+            hop: win / 2,
+            seek: frames_for(SEEK_MS),
+            input: VecDeque::new(),
+            input_start: 0,
+            nominal: 0.0,
+            natural: None,
+            overlap: vec![0.0; win / 2 * channels],
+            out: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    fn input_frames(&self) -> usize {
+        self.input.len() / self.channels
+    }
+
+    /// Pull from the inner source until frame `upto` (absolute, exclusive)
+    /// is buffered or the inner source ends.
+    fn fill_to(&mut self, upto: usize) {
+        while !self.inner_done && self.input_start + self.input_frames() < upto {
+            for _ in 0..self.channels {
+                match self.inner.next() {
+                    Some(v) => self.input.push_back(v),
+                    None => {
+                        self.inner_done = true;
+                        break;
+                    }
+                }
+            }
+        }
+        // Drop a trailing partial frame so indexing stays frame-aligned.
+        let whole = self.input_frames() * self.channels;
+        self.input.truncate(whole);
+    }
+
+    /// Sample `c` of absolute frame `f`; silence outside the buffer.
+    fn sample(&self, f: usize, c: usize) -> f32 {
+        f.checked_sub(self.input_start)
+            .and_then(|rel| self.input.get(rel * self.channels + c))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Channel sum of absolute frame `f`, for correlation.
+    fn mono(&self, f: usize) -> f32 {
+        (0..self.channels).map(|c| self.sample(f, c)).sum()
+    }
+
+    /// Start in `nominal ± seek` whose first half best matches the
+    /// continuation of the previous segment.
+    fn best_start(&self, nominal: usize, natural: usize) -> usize {
+        let lo = nominal.saturating_sub(self.seek).max(self.input_start);
+        let hi = nominal + self.seek;
+        let reference: Vec<f32> = (0..self.hop)
+            .step_by(CORR_STRIDE)
+            .map(|i| self.mono(natural + i))
+            .collect();
+        let mut best = (nominal, f32::MIN);
+        for start in lo..=hi {
+            let score: f32 = reference
+                .iter()
+                .zip((0..self.hop).step_by(CORR_STRIDE))
+                .map(|(r, i)| r * self.mono(start + i))
+                .sum();
+            if score > best.1 {
+                best = (start, score);
+            }
+        }
+        best.0
+    }
+
+    /// Produce the next `hop` frames of output. `false` once nothing is
+    /// left to produce.
+    fn step(&mut self) -> bool {
+        let nominal = self.nominal.round() as usize;
+        self.fill_to(nominal + self.seek + self.win + self.hop);
+        let input_end = self.input_start + self.input_frames();
+        if self.inner_done && nominal >= input_end {
+            if self.finished {
+                return false;
+            }
+            // Let the last segment's tail fade out.
+            self.out.extend(self.overlap.drain(..));
+            self.finished = true;
+            return true;
+        }
+
+        let start = match self.natural {
+            Some(natural) => self.best_start(nominal, natural),
+            None => nominal,
+        };
+        let ch = self.channels;
+        for i in 0..self.win {
+            let w = self.window[i];
+            for c in 0..ch {
+                let v = self.sample(start + i, c) * w;
+                if i < self.hop {
+                    self.out.push_back(self.overlap[i * ch + c] + v);
+                } else {
+                    self.overlap[(i - self.hop) * ch + c] = v;
+                }
+            }
+        }
+        self.natural = Some(start + self.hop);
+        self.nominal += self.hop as f64 * self.speed;
+
+        // Keep only what the next search can still reach.
+        let keep_from = (self.nominal.round() as usize)
+            .saturating_sub(self.seek)
+            .min(start + self.hop);
+        let drop = keep_from
+            .saturating_sub(self.input_start)
+            .min(self.input_frames());
+        self.input.drain(..drop * ch);
+        self.input_start += drop;
+        true
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for TimeStretch<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        while self.out.is_empty() {
+            if !self.step() {
+                return None;
+            }
+        }
+        self.out.pop_front()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for TimeStretch<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
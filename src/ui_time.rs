@@ -7,15 +7,61 @@ pub fn format_clock(content_secs: u64, total_secs: u64) -> String {
     format!("{} / {}", fmt_c, fmt_t)
 }
 
+/// [`format_clock`] with milliseconds: `MM:SS.mmm / MM:SS.mmm`.
+pub fn format_clock_ms(content_secs: f64, total_secs: f64) -> String {
+    let hours = total_secs >= 3600.0;
+    format!(
+        "{} / {}",
+        fmt_millis(content_secs, hours),
+        fmt_millis(total_secs, hours)
+    )
+}
+
+/// A single position with milliseconds, `MM:SS.mmm` or `HH:MM:SS.mmm`.
+pub fn format_span_ms(secs: f64) -> String {
+    fmt_millis(secs, secs >= 3600.0)
+}
+
+/// Time left as `-MM:SS`, or `-HH:MM:SS` when the clock for `total_secs`
+/// shows hours, so the two line up.
+pub fn format_remaining(remaining_secs: u64, total_secs: u64) -> String {
+    if total_secs >= 3600 {
+        format!("-{}", fmt_hms(remaining_secs))
+    } else {
+        format!("-{}", fmt_ms(remaining_secs))
+    }
+}
+
+/// A single duration, in `MM:SS` or `HH:MM:SS` once it reaches an hour.
+pub fn format_span(secs: u64) -> String {
+    if secs >= 3600 {
+        fmt_hms(secs)
+    } else {
+        fmt_ms(secs)
+    }
+}
+
 fn fmt_ms(secs: u64) -> String {
     let m = secs / 60;
     let s = secs % 60;
     format!("{:02}:{:02}", m, s)
 }
 
+/// Always `HH:MM:SS`, for lists read by other tools.
+pub fn format_hms(secs: u64) -> String {
+    fmt_hms(secs)
+}
+
 fn fmt_hms(secs: u64) -> String {
     let h = secs / 3600;
     let m = (secs % 3600) / 60;
     let s = secs % 60;
     format!("{:02}:{:02}:{:02}", h, m, s)
 }
+
+fn fmt_millis(secs: f64, hours: bool) -> String {
+    let ms = (secs.max(0.0) * 1000.0).floor() as u64;
+    let whole = ms / 1000;
+    let clock = if hours { fmt_hms(whole) } else { fmt_ms(whole) };
+    format!("{}.{:03}", clock, ms % 1000)
+}
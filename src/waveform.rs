@@ -0,0 +1,142 @@
+//! Waveform overview of the loaded file, drawn above the progress bar.
+//!
+//! A fixed-resolution min/max envelope is computed once per file on a
+//! worker thread; drawing merges its buckets down to the widget's width,
+//! redone only when the width changes.
+
+use crate::audio::SampleStore;
+use eframe::egui;
+use egui::Color32;
+use log::warn;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// Envelope buckets per file; plenty for any window width.
+const ENVELOPE_BUCKETS: usize = 8192;
+const PLAYED_COLOR: Color32 = Color32::from_rgb(40, 110, 200);
+const UNPLAYED_COLOR: Color32 = Color32::from_gray(120);
+const PLAYHEAD_COLOR: Color32 = Color32::from_rgb(255, 170, 0);
+
+type Peak = (f32, f32); // (min, max)
+
+pub struct Waveform {
+    rx: Option<Receiver<Vec<Peak>>>,
+    peaks: Vec<Peak>,
+    scaled: (usize, Vec<Peak>), // for this many pixel columns
+}
+
+impl Waveform {
+    /// Start computing the envelope of `samples` in the background.
+    /// `None` for streamed files, which would have to be decoded in full.
+    pub fn compute(samples: &SampleStore) -> Option<Self> {
+        if matches!(samples, SampleStore::Streamed(_)) {
+            return None;
+        }
+        let samples = samples.clone();
+        let (tx, rx) = mpsc::channel();
+        let spawned = thread::Builder::new()
+            .name("waveform".into())
+            .spawn(move || {
+                let _ = tx.send(envelope(&samples));
+            });
+        if let Err(e) = spawned {
+            warn!("Failed to spawn waveform thread: {}", e);
+            return None;
+        }
+        Some(Self {
+            rx: Some(rx),
+            peaks: Vec::new(),
+            scaled: (0, Vec::new()),
+        })
+    }
+
+    /// Draw the waveform with the playhead at `fraction` across; the
+    /// response senses clicks and drags for seeking. `None` until the
+    /// envelope is ready.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        height: f32,
+        fraction: f32,
+    ) -> Option<egui::Response> {
+        if let Some(rx) = &self.rx {
+            match rx.try_recv() {
+                Ok(peaks) => {
+                    self.peaks = peaks;
+                    self.rx = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => self.rx = None,
+            }
+        }
+        if self.peaks.is_empty() {
+            return None;
+        }
+
+        let size = egui::vec2(ui.available_width(), height);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let columns = rect.width().max(1.0) as usize;
+        if self.scaled.0 != columns {
+            self.scaled = (columns, rescale(&self.peaks, columns));
+        }
+
+        let painter = ui.painter_at(rect);
+        let mid = rect.center().y;
+        let half = rect.height() / 2.0;
+        let playhead = rect.left() + rect.width() * fraction.clamp(0.0, 1.0);
+        for (i, &(lo, hi)) in self.scaled.1.iter().enumerate() {
+            let x = rect.left() + i as f32 + 0.5;
+            let color = if x <= playhead {
+                PLAYED_COLOR
+            } else {
+                UNPLAYED_COLOR
+            };
+            // At least a pixel, so silence still shows as a line.
+            let top = mid - hi.clamp(-1.0, 1.0) * half;
+            let bottom = (mid - lo.clamp(-1.0, 1.0) * half).max(top + 1.0);
+            painter.line_segment(
+                [egui::pos2(x, top), egui::pos2(x, bottom)],
+                egui::Stroke::new(1.0, color),
+            );
+        }
+        painter.line_segment(
+            [
+                egui::pos2(playhead, rect.top()),
+                egui::pos2(playhead, rect.bottom()),
+            ],
+            egui::Stroke::new(2.0, PLAYHEAD_COLOR),
+        );
+        Some(response)
+    }
+}
+
+/// Min/max over `ENVELOPE_BUCKETS` equal slices of the interleaved
+/// samples (all channels together).
+fn envelope(samples: &SampleStore) -> Vec<Peak> {
+    let len = samples.len();
+    let buckets = ENVELOPE_BUCKETS.min(len);
+    (0..buckets)
+        .map(|b| {
+            let (start, end) = (b * len / buckets, (b + 1) * len / buckets);
+            (start..end).fold((0.0f32, 0.0f32), |(lo, hi), i| {
+                let v = samples.get(i);
+                (lo.min(v), hi.max(v))
+            })
+        })
+        .collect()
+}
+
+/// Merge `peaks` into `columns` buckets (repeating them when there are
+/// fewer peaks than columns).
+fn rescale(peaks: &[Peak], columns: usize) -> Vec<Peak> {
+    let n = peaks.len();
+    (0..columns)
+        .map(|c| {
+            let start = c * n / columns;
+            let end = ((c + 1) * n / columns).max(start + 1).min(n);
+            peaks[start..end]
+                .iter()
+                .fold((0.0f32, 0.0f32), |(lo, hi), &(a, b)| (lo.min(a), hi.max(b)))
+        })
+        .collect()
+}